@@ -0,0 +1,98 @@
+use crate::resources::*;
+
+use ggez::audio::Source;
+use ggez::Context;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+// Where the sounds AudioSystem queued end up
+pub trait AudioBackend {
+  fn play(&mut self, sound: &SoundPlay);
+}
+
+// Plays nothing and remembers everything, for headless runs, --mute and
+// builds without sound files. With --sound-log the list is written out at
+// the end, so a test can check which sounds a replay would have played.
+#[derive(Debug, Default)]
+pub struct NullAudio {
+  pub played: Vec<SoundPlay>,
+}
+
+impl NullAudio {
+  pub fn write_log(&self, path: &Path) -> Result<(), String> {
+    let file = File::create(path)
+      .map_err(|e| format!("Unable to write sound log {}: {}", path.display(), e))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &self.played)
+      .map_err(|e| format!("Unable to write sound log {}: {}", path.display(), e))
+  }
+}
+
+impl AudioBackend for NullAudio {
+  fn play(&mut self, sound: &SoundPlay) {
+    self.played.push(sound.clone());
+  }
+}
+
+// A ggez source can only play one thing at a time, so every sound gets as
+// many as it's allowed to play at once and they take turns
+struct SourcePool {
+  sources: Vec<Source>,
+  next: usize,
+}
+
+pub struct GgezAudio {
+  pools: HashMap<String, SourcePool>,
+}
+
+impl GgezAudio {
+  // None until there are sound files to play, so the game runs silent
+  // rather than complaining at launch. Sounds without a file stay quiet;
+  // files that are there but broken are reported once here.
+  pub fn load(ctx: &mut Context) -> Option<GgezAudio> {
+    let mut pools = HashMap::new();
+
+    for sound in SOUNDS {
+      if !ctx.filesystem.exists(sound.path) {
+        continue;
+      }
+
+      let sources: Result<Vec<Source>, _> = (0..sound.max_concurrent.max(1))
+        .map(|_| Source::new(ctx, sound.path))
+        .collect();
+
+      match sources {
+        Ok(sources) => {
+          pools.insert(sound.name.to_owned(), SourcePool { sources, next: 0 });
+        }
+        Err(e) => eprintln!("Unable to load sound {}: {}", sound.path, e),
+      }
+    }
+
+    if pools.is_empty() {
+      None
+    } else {
+      Some(GgezAudio { pools })
+    }
+  }
+}
+
+impl AudioBackend for GgezAudio {
+  fn play(&mut self, sound: &SoundPlay) {
+    let pool = match self.pools.get_mut(&sound.name) {
+      Some(pool) => pool,
+      None => return,
+    };
+
+    let index = pool.next;
+    pool.next = (index + 1) % pool.sources.len();
+    let source = &mut pool.sources[index];
+
+    source.set_volume(sound.volume);
+    source.set_pitch(sound.pitch);
+    if let Err(e) = source.play() {
+      eprintln!("Unable to play sound {}: {}", sound.name, e);
+    }
+  }
+}
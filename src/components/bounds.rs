@@ -1,6 +1,7 @@
+use serde::{Deserialize, Serialize};
 use specs::{Component, VecStorage};
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct BoundsComponent {
   pub x_min: f32,
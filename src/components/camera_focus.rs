@@ -0,0 +1,6 @@
+use serde::{Deserialize, Serialize};
+use specs::{Component, VecStorage};
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct CameraFocusComponent;
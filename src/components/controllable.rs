@@ -1,5 +1,13 @@
+use serde::{Deserialize, Serialize};
 use specs::{Component, VecStorage};
 
-#[derive(Component, Debug, Clone)]
+// player is which of the local players' Inputs drives the ship. boost and
+// brake are how hard the ship is currently boosting or braking, in
+// 0.0..=1.0. They're written by ControllableSystem each tick.
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
 #[storage(VecStorage)]
-pub struct ControllableComponent;
+pub struct ControllableComponent {
+  pub player: usize,
+  pub boost: f32,
+  pub brake: f32,
+}
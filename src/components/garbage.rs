@@ -1,13 +1,20 @@
+use serde::{Deserialize, Serialize};
 use specs::{Component, VecStorage};
 
-#[derive(Component, Debug, Clone)]
+// culled marks entities removed for leaving the world rather than dying,
+// which skips their OnDeathComponent effects.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct GarbageComponent {
   pub is_alive: bool,
+  pub culled: bool,
 }
 
 impl Default for GarbageComponent {
   fn default() -> GarbageComponent {
-    GarbageComponent { is_alive: true }
+    GarbageComponent {
+      is_alive: true,
+      culled: false,
+    }
   }
 }
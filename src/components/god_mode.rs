@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+use specs::{Component, VecStorage};
+
+// Damage is ignored while this is attached. Toggled from the console.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct GodModeComponent;
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+use specs::{Component, VecStorage};
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct HealthComponent {
+  pub hp: f32,
+  pub max_hp: f32,
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use specs::{Component, VecStorage};
+
+use crate::utils::*;
+use ggez::graphics::{Color, Point2};
+use std::time::Duration;
+
+// Added to an entity when it takes damage. The mesh is drawn white while
+// `flash` runs down and wobbles in scale while `wobble` runs down, then
+// its original color and scale are put back.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct HitFlashComponent {
+  pub flash: Duration,
+  pub wobble: Duration,
+  pub elapsed: Duration,
+  #[serde(with = "serde_option_color")]
+  pub original_color: Option<Color>,
+  #[serde(with = "serde_point2")]
+  pub original_scale: Point2,
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use specs::{Component, VecStorage};
+
+use crate::entities::*;
+use crate::resources::*;
+
+// Something that happens at an entity's position. Effects are queued as
+// EffectEvents and carried out by EffectSystem.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Effect {
+  Spawn(Prefab),
+  Sound(String),
+  AddScore(u32),
+  Event(GameEvent),
+  // An enemy driven by the named script
+  SpawnEnemy(String),
+  // Calls a function in a script, skipped if the script doesn't define it
+  Script { script: String, function: String },
+}
+
+// Effects run once, the first tick the entity exists
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct OnSpawnComponent {
+  pub effects: Vec<Effect>,
+}
+
+// Effects run when GarbageSystem deletes the entity, unless it was culled
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct OnDeathComponent {
+  pub effects: Vec<Effect>,
+}
@@ -1,8 +1,9 @@
+use serde::{Deserialize, Serialize};
 use specs::{Component, VecStorage};
 
 use std::time::Duration;
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct LifetimeComponent {
   pub duration: Duration,
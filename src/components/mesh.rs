@@ -1,10 +1,26 @@
+use serde::{Deserialize, Serialize};
 use specs::{Component, VecStorage};
 
-use ggez::graphics::{DrawParam, MeshBuilder};
+use ggez::graphics::DrawParam;
+
+// Identifies the geometry in a MeshComponent. Entities with the same key
+// share one mesh, built from the key by mesh_for the first time it's
+// drawn, so the key must change whenever the geometry does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MeshKey {
+  Player,
+  Projectile,
+  Ammo,
+  OutOfBoundsExplosion,
+  ExplosionLine { length: u32 },
+  TrailParticle { radius: u32 },
+  Particle,
+  Enemy { radius: u32 },
+}
 
 #[derive(Component, Debug, Clone)]
 #[storage(VecStorage)]
 pub struct MeshComponent {
-  pub mesh: MeshBuilder,
+  pub key: MeshKey,
   pub draw_param: DrawParam,
 }
@@ -1,23 +1,43 @@
 mod bounds;
+mod camera_focus;
 mod controllable;
 mod explode_bounds;
 mod garbage;
+mod god_mode;
+mod health;
+mod hit_flash;
+mod lifecycle;
 mod lifetime;
 mod mesh;
+mod owner;
+mod particle;
+mod pooled;
 mod position;
 mod powerup;
 mod rigid_body;
+mod script;
 mod shooting;
+mod trail;
 mod tween;
 
 pub use bounds::*;
+pub use camera_focus::*;
 pub use controllable::*;
 pub use explode_bounds::*;
 pub use garbage::*;
+pub use god_mode::*;
+pub use health::*;
+pub use hit_flash::*;
+pub use lifecycle::*;
 pub use lifetime::*;
 pub use mesh::*;
+pub use owner::*;
+pub use particle::*;
+pub use pooled::*;
 pub use position::*;
 pub use powerup::*;
 pub use rigid_body::*;
+pub use script::*;
 pub use shooting::*;
+pub use trail::*;
 pub use tween::*;
@@ -1,10 +1,54 @@
+use serde::{Deserialize, Serialize};
 use specs::{Component, VecStorage};
 
 use crate::components::*;
+use crate::utils::*;
+use ggez::graphics::Color;
 use std::time::Duration;
 
-#[derive(Component)]
+// A value that moves from start to end over a particle's life
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParticleCurve {
+  pub start: f32,
+  pub end: f32,
+  pub ease: Ease,
+}
+
+impl ParticleCurve {
+  pub fn at(&self, t: f32) -> f32 {
+    lerp(self.start, self.end, self.ease.ratio(t))
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColorCurve {
+  #[serde(with = "serde_color")]
+  pub start: Color,
+  #[serde(with = "serde_color")]
+  pub end: Color,
+  pub ease: Ease,
+}
+
+impl ColorCurve {
+  pub fn at(&self, t: f32) -> Color {
+    lerp_color(self.start, self.end, self.ease.ratio(t))
+  }
+}
+
+// Emits `burst` particles every `frequency` from `offset` (in the entity's
+// local space). Particles leave at `angle` relative to the entity, give or
+// take `spread`, with a speed picked from `speed`.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct ParticleEmitterComponent {
   pub frequency: Duration,
+  pub burst: u32,
+  pub offset: (f32, f32),
+  pub angle: f32,
+  pub spread: f32,
+  pub speed: (f32, f32),
+  pub lifetime: Duration,
+  pub size: ParticleCurve,
+  pub color: ColorCurve,
+  pub elapsed: Duration,
 }
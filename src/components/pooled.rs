@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+use specs::{Component, VecStorage};
+
+use crate::resources::*;
+
+// Handed back to its pool by GarbageSystem rather than deleted
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct PooledComponent {
+  pub kind: PoolKind,
+}
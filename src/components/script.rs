@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+use specs::{Component, VecStorage};
+use std::collections::BTreeMap;
+
+// Runs the named script's update function every tick. Rhai functions
+// can't keep state between calls, so whatever the script stores with
+// set_var() lives here.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct ScriptComponent {
+  pub script: String,
+  pub vars: BTreeMap<String, f64>,
+}
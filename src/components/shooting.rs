@@ -1,9 +1,11 @@
+use serde::{Deserialize, Serialize};
 use specs::{Component, VecStorage};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-#[derive(Component, Debug, Clone)]
+// The cooldown runs on simulated time so it replays the same every run
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct ShootingComponent {
   pub every: Duration,
-  pub last_shot_at: Instant,
+  pub since_last_shot: Duration,
 }
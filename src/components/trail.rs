@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+use specs::{Component, VecStorage};
+
+use std::time::Duration;
+
+// Leaves a trail particle at each attachment point (in the entity's local
+// space) every `frequency`.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct TrailComponent {
+  pub attachments: Vec<(f32, f32)>,
+  pub frequency: Duration,
+  pub elapsed: Duration,
+}
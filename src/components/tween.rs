@@ -1,26 +1,183 @@
+use serde::{Deserialize, Serialize};
 use specs::{Component, VecStorage};
 
+use crate::components::*;
+use crate::utils::*;
+use easer::functions::*;
+use ggez::graphics::Color;
 use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Easing {
+  Linear,
+  Quad,
+  Cubic,
+  Quart,
+  Quint,
+  Sine,
+  Expo,
+  Circ,
+  Back,
+  Elastic,
+  Bounce,
+}
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Ease {
-  Cubic,
+  In(Easing),
+  Out(Easing),
+  InOut(Easing),
+}
+
+macro_rules! ease {
+  ($easing:expr, $method:ident, $t:expr) => {
+    match $easing {
+      Easing::Linear => Linear::$method($t, 0.0, 1.0, 1.0),
+      Easing::Quad => Quad::$method($t, 0.0, 1.0, 1.0),
+      Easing::Cubic => Cubic::$method($t, 0.0, 1.0, 1.0),
+      Easing::Quart => Quart::$method($t, 0.0, 1.0, 1.0),
+      Easing::Quint => Quint::$method($t, 0.0, 1.0, 1.0),
+      Easing::Sine => Sine::$method($t, 0.0, 1.0, 1.0),
+      Easing::Expo => Expo::$method($t, 0.0, 1.0, 1.0),
+      Easing::Circ => Circ::$method($t, 0.0, 1.0, 1.0),
+      Easing::Back => Back::$method($t, 0.0, 1.0, 1.0),
+      Easing::Elastic => Elastic::$method($t, 0.0, 1.0, 1.0),
+      Easing::Bounce => Bounce::$method($t, 0.0, 1.0, 1.0),
+    }
+  };
+}
+
+impl Ease {
+  // Eased progress for t in 0.0..=1.0. Back and Elastic overshoot, so the
+  // result can fall slightly outside 0.0..=1.0.
+  pub fn ratio(&self, t: f32) -> f32 {
+    let t = t.max(0.0).min(1.0);
+    match *self {
+      Ease::In(easing) => ease!(easing, ease_in, t),
+      Ease::Out(easing) => ease!(easing, ease_out, t),
+      Ease::InOut(easing) => ease!(easing, ease_in_out, t),
+    }
+  }
 }
 
-#[derive(Debug, Clone)]
+// The property a tween animates, and the values it moves between.
+// Offset and rotation are relative to the entity's position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Tween {
   SizeTween {
-    ease: Ease,
     starting: f32, // percentage
     ending: f32,
   },
+  AlphaTween {
+    starting: f32,
+    ending: f32,
+  },
+  ColorTween {
+    #[serde(with = "serde_color")]
+    starting: Color,
+    #[serde(with = "serde_color")]
+    ending: Color,
+  },
+  OffsetTween {
+    starting: (f32, f32),
+    ending: (f32, f32),
+  },
+  RotationTween {
+    starting: f32,
+    ending: f32,
+  },
 }
 
-#[derive(Component, Debug, Clone)]
+// A tween tree, evaluated purely from how much time has elapsed.
+//
+// Sequence runs its children one after the other, Parallel runs them all
+// at once and lasts as long as the longest. Repeat plays its child `times`
+// times (forever if None), reversing every other play when `yoyo` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TweenNode {
+  Tween {
+    tween_type: Tween,
+    ease: Ease,
+    duration: Duration,
+  },
+  Delay(Duration),
+  Sequence(Vec<TweenNode>),
+  Parallel(Vec<TweenNode>),
+  Repeat {
+    node: Box<TweenNode>,
+    times: Option<u32>,
+    yoyo: bool,
+  },
+}
+
+impl TweenNode {
+  pub fn tween(tween_type: Tween, ease: Ease, duration: Duration) -> TweenNode {
+    TweenNode::Tween {
+      tween_type,
+      ease,
+      duration,
+    }
+  }
+
+  pub fn repeat(self, times: Option<u32>, yoyo: bool) -> TweenNode {
+    TweenNode::Repeat {
+      node: Box::new(self),
+      times,
+      yoyo,
+    }
+  }
+
+  // Total length in seconds, or None if the tree loops forever
+  pub fn duration(&self) -> Option<f32> {
+    match self {
+      TweenNode::Tween { duration, .. } => Some(seconds(*duration)),
+      TweenNode::Delay(duration) => Some(seconds(*duration)),
+      TweenNode::Sequence(nodes) => nodes
+        .iter()
+        .map(|n| n.duration())
+        .collect::<Option<Vec<f32>>>()
+        .map(|durations| durations.into_iter().sum()),
+      TweenNode::Parallel(nodes) => nodes
+        .iter()
+        .map(|n| n.duration())
+        .collect::<Option<Vec<f32>>>()
+        .map(|durations| durations.into_iter().fold(0.0, f32::max)),
+      TweenNode::Repeat { node, times, .. } => match (node.duration(), times) {
+        (Some(duration), Some(times)) => Some(duration * *times as f32),
+        _ => None,
+      },
+    }
+  }
+}
+
+// What to do once a tween tree has finished playing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TweenAction {
+  Garbage,
+  Effect(Effect),
+}
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct TweenComponent {
-  pub tween_type: Tween,
+  pub tween: TweenNode,
   pub elapsed: Duration,
-  pub duration: Duration,
+  pub on_complete: Vec<TweenAction>,
+  pub completed: bool,
+}
+
+impl TweenComponent {
+  pub fn new(tween: TweenNode) -> TweenComponent {
+    TweenComponent {
+      tween,
+      elapsed: Duration::from_millis(0),
+      on_complete: vec![],
+      completed: false,
+    }
+  }
+
+  pub fn on_complete(mut self, action: TweenAction) -> TweenComponent {
+    self.on_complete.push(action);
+    self
+  }
 }
@@ -0,0 +1,498 @@
+use crate::components::*;
+use crate::entities::*;
+use crate::resources::*;
+use crate::snapshot::{self, DEFAULT_SNAPSHOT_PATH};
+
+use ggez::graphics;
+use ggez::{Context, GameResult};
+use specs::prelude::*;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+pub const CONSOLE_HISTORY_PATH: &str = "console_history.txt";
+const MAX_HISTORY: usize = 100;
+const MAX_OUTPUT: usize = 12;
+const LINE_HEIGHT: f32 = 14.0;
+
+type CommandHandler = fn(&mut World, &[&str]) -> Result<String, String>;
+
+struct Command {
+  name: &'static str,
+  usage: &'static str,
+  handler: CommandHandler,
+  // Changes the simulation, which a networked peer wouldn't see
+  changes_world: bool,
+}
+
+const COMMANDS: &[Command] = &[
+  Command {
+    name: "help",
+    usage: "help",
+    handler: help,
+    changes_world: false,
+  },
+  Command {
+    name: "spawn",
+    usage: "spawn <projectile|ammo|death_explosion|out_of_bounds_explosion> [x y]",
+    handler: spawn,
+    changes_world: true,
+  },
+  Command {
+    name: "enemy",
+    usage: "enemy <script> [x y]",
+    handler: enemy,
+    changes_world: true,
+  },
+  Command {
+    name: "set",
+    usage: "set <hp|max_hp|fire_rate_ms> <value>",
+    handler: set,
+    changes_world: true,
+  },
+  Command {
+    name: "god",
+    usage: "god",
+    handler: god,
+    changes_world: true,
+  },
+  Command {
+    name: "timescale",
+    usage: "timescale <scale>",
+    handler: timescale,
+    changes_world: true,
+  },
+  Command {
+    name: "volume",
+    usage: "volume <master|weapons|pickups|impacts|explosions> [0..1]",
+    handler: volume,
+    changes_world: false,
+  },
+  Command {
+    name: "list",
+    usage: "list <component>",
+    handler: list,
+    changes_world: false,
+  },
+  Command {
+    name: "reset_director",
+    usage: "reset_director",
+    handler: reset_director,
+    changes_world: true,
+  },
+  Command {
+    name: "save",
+    usage: "save [path]",
+    handler: save,
+    changes_world: false,
+  },
+  Command {
+    name: "load",
+    usage: "load [path]",
+    handler: load,
+    changes_world: true,
+  },
+];
+
+// A drop-down console, toggled with the backtick key. While it's open it
+// takes all keyboard input.
+pub struct Console {
+  pub open: bool,
+  line: String,
+  output: Vec<String>,
+  history: Vec<String>,
+  // Where Up/Down are in the history, None while editing a fresh line
+  browsing: Option<usize>,
+}
+
+impl Console {
+  // Picks up the history left by previous sessions, if there is any
+  pub fn load() -> Console {
+    let history: Vec<String> = fs::read_to_string(CONSOLE_HISTORY_PATH)
+      .map(|contents| contents.lines().map(|line| line.to_owned()).collect())
+      .unwrap_or_else(|_| vec![]);
+    let skip = history.len().saturating_sub(MAX_HISTORY);
+
+    Console {
+      open: false,
+      line: String::new(),
+      output: vec![],
+      history: history.into_iter().skip(skip).collect(),
+      browsing: None,
+    }
+  }
+
+  pub fn toggle(&mut self) {
+    self.open = !self.open;
+  }
+
+  pub fn type_text(&mut self, text: &str) {
+    // The toggle key also arrives as text
+    self.line.extend(text.chars().filter(|c| *c != '`'));
+  }
+
+  pub fn backspace(&mut self) {
+    self.line.pop();
+  }
+
+  pub fn history_up(&mut self) {
+    if self.history.is_empty() {
+      return;
+    }
+    let index = match self.browsing {
+      Some(index) => index.saturating_sub(1),
+      None => self.history.len() - 1,
+    };
+    self.browsing = Some(index);
+    self.line = self.history[index].clone();
+  }
+
+  pub fn history_down(&mut self) {
+    match self.browsing {
+      Some(index) if index + 1 < self.history.len() => {
+        self.browsing = Some(index + 1);
+        self.line = self.history[index + 1].clone();
+      }
+      _ => {
+        self.browsing = None;
+        self.line.clear();
+      }
+    }
+  }
+
+  // Runs whatever has been typed against the world. Networked, only
+  // commands that leave the simulation alone are allowed.
+  pub fn submit(&mut self, world: &mut World, networked: bool) {
+    let line = self.line.trim().to_owned();
+    self.line.clear();
+    self.browsing = None;
+
+    if line.is_empty() {
+      return;
+    }
+
+    self.print(format!("> {}", line));
+    match execute(world, &line, networked) {
+      Ok(output) => self.print(output),
+      Err(error) => self.print(format!("error: {}", error)),
+    }
+
+    self.history.push(line.clone());
+    if self.history.len() > MAX_HISTORY {
+      self.history.remove(0);
+    }
+    if let Err(e) = append_history(&line) {
+      self.print(format!("error: couldn't save history: {}", e));
+    }
+  }
+
+  pub fn draw(&self, ctx: &mut Context, font: &graphics::Font, width: f32) -> GameResult<()> {
+    if !self.open {
+      return Ok(());
+    }
+
+    let height = (MAX_OUTPUT + 1) as f32 * LINE_HEIGHT + 8.0;
+    graphics::set_color(ctx, graphics::Color::new(0.0, 0.0, 0.0, 0.8))?;
+    graphics::rectangle(
+      ctx,
+      graphics::DrawMode::Fill,
+      graphics::Rect::new(0.0, 0.0, width, height),
+    )?;
+
+    graphics::set_color(ctx, graphics::WHITE)?;
+    let prompt = format!("> {}_", self.line);
+    let skip = self.output.len().saturating_sub(MAX_OUTPUT);
+    let lines = self
+      .output
+      .iter()
+      .skip(skip)
+      .chain(std::iter::once(&prompt));
+
+    for (i, line) in lines.enumerate() {
+      let text = graphics::Text::new(ctx, line, font)?;
+      let dest = graphics::Point2::new(4.0, 4.0 + i as f32 * LINE_HEIGHT);
+      graphics::draw(ctx, &text, dest, 0.0)?;
+    }
+
+    Ok(())
+  }
+
+  fn print(&mut self, output: String) {
+    self
+      .output
+      .extend(output.lines().map(|line| line.to_owned()));
+  }
+}
+
+fn append_history(line: &str) -> std::io::Result<()> {
+  let mut file = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(CONSOLE_HISTORY_PATH)?;
+  writeln!(file, "{}", line)
+}
+
+pub fn execute(world: &mut World, line: &str, networked: bool) -> Result<String, String> {
+  let words: Vec<&str> = line.split_whitespace().collect();
+  let (name, args) = match words.split_first() {
+    Some((name, args)) => (*name, args),
+    None => return Ok(String::new()),
+  };
+
+  match COMMANDS.iter().find(|command| command.name == name) {
+    Some(command) if networked && command.changes_world => Err(format!(
+      "'{}' would desync the peer, it can't be used in a networked game",
+      name
+    )),
+    Some(command) => {
+      (command.handler)(world, args).map_err(|error| format!("{}\nusage: {}", error, command.usage))
+    }
+    None => Err(format!("unknown command '{}', try 'help'", name)),
+  }
+}
+
+fn parse_arg<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+  value
+    .parse::<T>()
+    .map_err(|_| format!("invalid value '{}'", value))
+}
+
+fn players(world: &World) -> Vec<Entity> {
+  let entities = world.entities();
+  let controllable = world.read_storage::<ControllableComponent>();
+  (&entities, &controllable).join().map(|(e, _)| e).collect()
+}
+
+fn help(_world: &mut World, _args: &[&str]) -> Result<String, String> {
+  let usages: Vec<&str> = COMMANDS.iter().map(|command| command.usage).collect();
+  Ok(usages.join("\n"))
+}
+
+// Spawns through the effect system, so a console spawn behaves exactly like
+// one from gameplay. Without a position it spawns on the player.
+fn spawn(world: &mut World, args: &[&str]) -> Result<String, String> {
+  let prefab = match args.first() {
+    Some(name) => name.parse::<Prefab>()?,
+    None => return Err("missing prefab".to_owned()),
+  };
+
+  let position = match args.len() {
+    1 => {
+      let player = players(world)
+        .into_iter()
+        .next()
+        .ok_or_else(|| "no player to spawn on, give a position".to_owned())?;
+      let position = world.read_storage::<PositionComponent>();
+      position
+        .get(player)
+        .cloned()
+        .ok_or("player has no position")?
+    }
+    3 => PositionComponent {
+      x: parse_arg(args[1])?,
+      y: parse_arg(args[2])?,
+      angle: 0.0,
+    },
+    _ => return Err("expected a prefab and an optional x y".to_owned()),
+  };
+
+  let (x, y) = (position.x, position.y);
+  world
+    .write_resource::<EffectEvents>()
+    .single_write(EffectEvent {
+      effect: Effect::Spawn(prefab),
+      position,
+    });
+
+  Ok(format!("spawned {:?} at {:.0}, {:.0}", prefab, x, y))
+}
+
+// Unknown scripts are reported by ScriptSystem when it gets to them
+fn enemy(world: &mut World, args: &[&str]) -> Result<String, String> {
+  let script = match args.first() {
+    Some(script) => script.to_string(),
+    None => return Err("missing script".to_owned()),
+  };
+
+  let position = match args.len() {
+    1 => {
+      let player = players(world)
+        .into_iter()
+        .next()
+        .ok_or_else(|| "no player to spawn on, give a position".to_owned())?;
+      let position = world.read_storage::<PositionComponent>();
+      position
+        .get(player)
+        .cloned()
+        .ok_or("player has no position")?
+    }
+    3 => PositionComponent {
+      x: parse_arg(args[1])?,
+      y: parse_arg(args[2])?,
+      angle: 0.0,
+    },
+    _ => return Err("expected a script and an optional x y".to_owned()),
+  };
+
+  let (x, y) = (position.x, position.y);
+  world
+    .write_resource::<EffectEvents>()
+    .single_write(EffectEvent {
+      effect: Effect::SpawnEnemy(script.clone()),
+      position,
+    });
+
+  Ok(format!("spawned {} enemy at {:.0}, {:.0}", script, x, y))
+}
+
+fn set(world: &mut World, args: &[&str]) -> Result<String, String> {
+  if args.len() != 2 {
+    return Err("expected a stat and a value".to_owned());
+  }
+  let (stat, value) = (args[0], args[1]);
+  let players = players(world);
+
+  match stat {
+    "hp" | "max_hp" => {
+      let value: f32 = parse_arg(value)?;
+      let mut health = world.write_storage::<HealthComponent>();
+      for player in players.iter() {
+        if let Some(health) = health.get_mut(*player) {
+          if stat == "hp" {
+            health.hp = value;
+          } else {
+            health.max_hp = value;
+          }
+        }
+      }
+    }
+    "fire_rate_ms" => {
+      let value: u64 = parse_arg(value)?;
+      let mut shooting = world.write_storage::<ShootingComponent>();
+      for player in players.iter() {
+        if let Some(shooting) = shooting.get_mut(*player) {
+          shooting.every = Duration::from_millis(value);
+        }
+      }
+    }
+    _ => return Err(format!("unknown stat '{}'", stat)),
+  }
+
+  Ok(format!("{} = {}", stat, value))
+}
+
+fn god(world: &mut World, _args: &[&str]) -> Result<String, String> {
+  let players = players(world);
+  let mut god_mode = world.write_storage::<GodModeComponent>();
+  let enable = players.iter().any(|player| !god_mode.contains(*player));
+
+  for player in players {
+    if enable {
+      god_mode.insert(player, GodModeComponent).unwrap();
+    } else {
+      god_mode.remove(player);
+    }
+  }
+
+  Ok(format!("god mode {}", if enable { "on" } else { "off" }))
+}
+
+fn timescale(world: &mut World, args: &[&str]) -> Result<String, String> {
+  let scale: f32 = match args.first() {
+    Some(value) => parse_arg(value)?,
+    None => {
+      return Ok(format!(
+        "timescale is {}",
+        world.read_resource::<TimeScale>().0
+      ))
+    }
+  };
+
+  if scale < 0.0 {
+    return Err("timescale can't be negative".to_owned());
+  }
+
+  world.write_resource::<TimeScale>().0 = scale;
+  Ok(format!("timescale = {}", scale))
+}
+
+fn volume(world: &mut World, args: &[&str]) -> Result<String, String> {
+  let name = match args.first() {
+    Some(name) => *name,
+    None => return Err("missing name".to_owned()),
+  };
+  let target = parse_volume_target(name)?;
+
+  let volume: f32 = match args.get(1) {
+    Some(value) => parse_arg(value)?,
+    None => {
+      return Ok(format!(
+        "{} volume is {}",
+        name,
+        world.read_resource::<AudioSettings>().volume(target)
+      ))
+    }
+  };
+
+  if volume < 0.0 || volume > 1.0 {
+    return Err("volume must be between 0 and 1".to_owned());
+  }
+
+  world
+    .write_resource::<AudioSettings>()
+    .set_volume(target, volume);
+  Ok(format!("{} volume = {}", name, volume))
+}
+
+fn list(world: &mut World, args: &[&str]) -> Result<String, String> {
+  let entities = match args.first() {
+    Some(&"position") => entities_with::<PositionComponent>(world),
+    Some(&"mesh") => entities_with::<MeshComponent>(world),
+    Some(&"rigid_body") => entities_with::<RigidBodyComponent>(world),
+    Some(&"health") => entities_with::<HealthComponent>(world),
+    Some(&"controllable") => entities_with::<ControllableComponent>(world),
+    Some(&"shooting") => entities_with::<ShootingComponent>(world),
+    Some(&"garbage") => entities_with::<GarbageComponent>(world),
+    Some(&"lifetime") => entities_with::<LifetimeComponent>(world),
+    Some(&"tween") => entities_with::<TweenComponent>(world),
+    Some(&"bounds") => entities_with::<BoundsComponent>(world),
+    Some(&"trail") => entities_with::<TrailComponent>(world),
+    Some(&"pooled") => entities_with::<PooledComponent>(world),
+    Some(&"owner") => entities_with::<OwnerComponent>(world),
+    Some(&"god_mode") => entities_with::<GodModeComponent>(world),
+    Some(&"script") => entities_with::<ScriptComponent>(world),
+    Some(other) => return Err(format!("unknown component '{}'", other)),
+    None => return Err("missing component".to_owned()),
+  };
+
+  let ids: Vec<String> = entities.iter().map(|e| e.id().to_string()).collect();
+  Ok(format!("{} entities: {}", ids.len(), ids.join(" ")))
+}
+
+fn entities_with<C: Component>(world: &World) -> Vec<Entity> {
+  let entities = world.entities();
+  let storage = world.read_storage::<C>();
+  (&entities, &storage).join().map(|(e, _)| e).collect()
+}
+
+// The spawn director is SpawnSystem's SpawnInfo plus the director
+// script's vars: forgetting what they have spawned lets them start
+// filling the world again, from the first wave.
+fn reset_director(world: &mut World, _args: &[&str]) -> Result<String, String> {
+  *world.write_resource::<SpawnInfo>() = SpawnInfo::default();
+  world.write_resource::<ScriptVars>().0.clear();
+  Ok("director reset".to_owned())
+}
+
+fn save(world: &mut World, args: &[&str]) -> Result<String, String> {
+  let path = Path::new(args.first().cloned().unwrap_or(DEFAULT_SNAPSHOT_PATH));
+  snapshot::save(world, path)?;
+  Ok(format!("saved to {}", path.display()))
+}
+
+fn load(world: &mut World, args: &[&str]) -> Result<String, String> {
+  let path = Path::new(args.first().cloned().unwrap_or(DEFAULT_SNAPSHOT_PATH));
+  snapshot::load(world, path)?;
+  Ok(format!("loaded {}", path.display()))
+}
@@ -0,0 +1,106 @@
+use crate::components::*;
+use crate::utils::*;
+
+use ggez::graphics;
+
+pub const PLAYER_SIZE: f32 = 25.0;
+pub const PROJECTILE_RADIUS: f32 = 4.0;
+pub const AMMO_RADIUS: f32 = 15.0;
+const OUT_OF_BOUNDS_EXPLOSION_SIZE: f32 = 20.0;
+
+// The geometry each MeshKey stands for. Everything that needs a mesh goes
+// through here, so an entity can always be rebuilt from its key alone.
+pub fn mesh_for(key: MeshKey) -> graphics::MeshBuilder {
+  let mut mesh = graphics::MeshBuilder::new();
+
+  match key {
+    MeshKey::Player => {
+      let size = PLAYER_SIZE;
+      mesh
+        .polygon(
+          graphics::DrawMode::Line(1.0),
+          &[
+            graphics::Point2::new(size, 0.0),
+            graphics::Point2::new(size / 2.0, -size / 2.0),
+            graphics::Point2::new(-size / 2.0, -size / 2.0),
+            graphics::Point2::new(-size, 0.0),
+            graphics::Point2::new(-size / 2.0, size / 2.0),
+            graphics::Point2::new(size / 2.0, size / 2.0),
+          ],
+        )
+        .polygon(
+          graphics::DrawMode::Line(1.0),
+          &[
+            graphics::Point2::new(size / 2.0, -size / 2.0),
+            graphics::Point2::new(0.0, -size),
+            graphics::Point2::new(-size - size / 2.0, -size),
+            graphics::Point2::new(-3.0 * size / 4.0, -size / 4.0),
+            graphics::Point2::new(-size / 2.0, -size / 2.0),
+          ],
+        )
+        .polygon(
+          graphics::DrawMode::Line(1.0),
+          &[
+            graphics::Point2::new(size / 2.0, size / 2.0),
+            graphics::Point2::new(-size / 2.0, size / 2.0),
+            graphics::Point2::new(-3.0 * size / 4.0, size / 4.0),
+            graphics::Point2::new(-size - size / 2.0, size),
+            graphics::Point2::new(0.0, size),
+          ],
+        );
+    }
+    MeshKey::Projectile => {
+      mesh.circle(
+        graphics::DrawMode::Line(1.0),
+        graphics::Point2::origin(),
+        PROJECTILE_RADIUS,
+        0.1,
+      );
+    }
+    MeshKey::Ammo => {
+      let radius = AMMO_RADIUS;
+      let rect = graphics::Rect::new(-radius, -radius, radius, radius);
+      mesh.polygon(graphics::DrawMode::Line(3.0), &rect_to_polygon(rect));
+    }
+    MeshKey::OutOfBoundsExplosion => {
+      let size = OUT_OF_BOUNDS_EXPLOSION_SIZE;
+      let rect = graphics::Rect::new(-size / 2.0, -size / 2.0, size, size);
+      mesh.polygon(graphics::DrawMode::Fill, &rect_to_polygon(rect));
+    }
+    MeshKey::ExplosionLine { length } => {
+      mesh.line(
+        &[
+          graphics::Point2::origin(),
+          graphics::Point2::new(length as f32, 0.0),
+        ],
+        2.0,
+      );
+    }
+    MeshKey::TrailParticle { radius } => {
+      mesh.circle(
+        graphics::DrawMode::Fill,
+        graphics::Point2::origin(),
+        radius as f32,
+        0.1,
+      );
+    }
+    // Particles are one unit square, scaled and tinted per draw
+    MeshKey::Particle => {
+      let rect = graphics::Rect::new(-0.5, -0.5, 1.0, 1.0);
+      mesh.polygon(graphics::DrawMode::Fill, &rect_to_polygon(rect));
+    }
+    // A hexagon, pointing along the enemy's heading
+    MeshKey::Enemy { radius } => {
+      let radius = radius as f32;
+      let points: Vec<graphics::Point2> = (0..6)
+        .map(|i| {
+          let angle = i as f32 * std::f32::consts::PI / 3.0;
+          graphics::Point2::new(angle.cos() * radius, angle.sin() * radius)
+        })
+        .collect();
+      mesh.polygon(graphics::DrawMode::Line(1.0), &points);
+    }
+  }
+
+  mesh
+}
@@ -11,32 +11,123 @@ use ncollide2d::world::CollisionGroups;
 use nphysics2d::math::Velocity;
 use nphysics2d::object::{BodyHandle, BodyStatus, ColliderDesc, RigidBodyDesc};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use specs::world::*;
 use specs::*;
-use std::time::{Duration, Instant};
+use std::str::FromStr;
+use std::time::Duration;
 
+mod meshes;
+
+pub use meshes::*;
+
+// Anything that can be spawned by name at a position, e.g. from a tween
+// completing or an entity dying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Prefab {
+  Projectile,
+  Ammo,
+  DeathExplosion,
+  OutOfBoundsExplosion,
+}
+
+impl FromStr for Prefab {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Prefab, String> {
+    match name {
+      "projectile" => Ok(Prefab::Projectile),
+      "ammo" => Ok(Prefab::Ammo),
+      "death_explosion" => Ok(Prefab::DeathExplosion),
+      "out_of_bounds_explosion" => Ok(Prefab::OutOfBoundsExplosion),
+      _ => Err(format!("unknown prefab '{}'", name)),
+    }
+  }
+}
+
+pub fn spawn_prefab(
+  prefab: Prefab,
+  entities: &EntitiesRes,
+  lazy: &LazyUpdate,
+  settings: &GameSettings,
+  physics: &mut PhysicsSim,
+  pools: &mut Pools,
+  rng: &mut GameRng,
+  position_component: PositionComponent,
+) -> () {
+  match prefab {
+    Prefab::Projectile => create_projectile(
+      entities,
+      lazy,
+      settings,
+      physics,
+      pools,
+      None,
+      position_component,
+    ),
+    Prefab::Ammo => {
+      let direction = if rng.0.gen() { -1.0 } else { 1.0 };
+      create_ammo_at(
+        entities,
+        lazy,
+        settings,
+        physics,
+        rng,
+        position_component.x,
+        position_component.y,
+        direction,
+      )
+    }
+    Prefab::DeathExplosion => create_death_explosion(
+      entities,
+      lazy,
+      physics,
+      pools,
+      rng,
+      position_component.x,
+      position_component.y,
+    ),
+    Prefab::OutOfBoundsExplosion => {
+      create_out_of_bounds_explosion(entities, lazy, position_component)
+    }
+  }
+}
+
+// There's only the one ship for now
+pub const PLAYER_SHIP: &str = "Fighter";
+
+// Gap between ships when more than one player starts
+const PLAYER_SPACING: f32 = 120.0;
+
+// player is the index of the Input that steers the ship
 pub fn create_player(
-  _ctx: &mut Context,
   entities: &EntitiesRes,
   lazy: &LazyUpdate,
   game_settings: &GameSettings,
   physics: &mut PhysicsSim,
+  player: usize,
 ) -> () {
-  let x = game_settings.width as f32 / 2.0;
-  let y = game_settings.height as f32 / 2.0;
+  // Players start side by side around the centre of the world
+  let offset = player as f32 - (game_settings.players as f32 - 1.0) / 2.0;
+  let x = game_settings.world_width as f32 / 2.0 + offset * PLAYER_SPACING;
+  let y = game_settings.world_height as f32 / 2.0;
   let velocity = 0.0;
   let angle = 0.5;
-  let size = 25.0;
-
+  let size = PLAYER_SIZE;
+
+  // Without friendly fire no player's projectiles can touch any ship,
+  // with it only a ship's own projectiles pass through it
+  let blacklist = if game_settings.friendly_fire {
+    player_projectile_group(player)
+  } else {
+    PLAYER_PROJECTILE_COLLISION_GROUP
+  };
   let collision_groups = CollisionGroups::new()
-    .with_membership(&[PLAYER_BODY_COLLISION_GROUP])
-    .with_blacklist(&[PLAYER_PROJECTILE_COLLISION_GROUP]);
+    .with_membership(&[PLAYER_BODY_COLLISION_GROUP, player_body_group(player)])
+    .with_blacklist(&[blacklist]);
 
-  let collider_desc = ColliderDesc::new(ShapeHandle::new(Ball::new(size)))
-    .collision_groups(collision_groups)
-    .name(ColliderType::Player.to_string());;
-
-  collider_desc.build(&mut physics.world);
+  let collider_desc =
+    ColliderDesc::new(ShapeHandle::new(Ball::new(size))).collision_groups(collision_groups);
 
   let rigid_body_handle = RigidBodyDesc::new()
     .collider(&collider_desc)
@@ -52,53 +143,69 @@ pub fn create_player(
 
   let position_component = PositionComponent { x, y, angle };
 
-  let mesh = graphics::MeshBuilder::new()
-    .polygon(
-      graphics::DrawMode::Line(1.0),
-      &[
-        graphics::Point2::new(size, 0.0),
-        graphics::Point2::new(size / 2.0, -size / 2.0),
-        graphics::Point2::new(-size / 2.0, -size / 2.0),
-        graphics::Point2::new(-size, 0.0),
-        graphics::Point2::new(-size / 2.0, size / 2.0),
-        graphics::Point2::new(size / 2.0, size / 2.0),
-      ],
-    )
-    .polygon(
-      graphics::DrawMode::Line(1.0),
-      &[
-        graphics::Point2::new(size / 2.0, -size / 2.0),
-        graphics::Point2::new(0.0, -size),
-        graphics::Point2::new(-size - size / 2.0, -size),
-        graphics::Point2::new(-3.0 * size / 4.0, -size / 4.0),
-        graphics::Point2::new(-size / 2.0, -size / 2.0),
-      ],
-    )
-    .polygon(
-      graphics::DrawMode::Line(1.0),
-      &[
-        graphics::Point2::new(size / 2.0, size / 2.0),
-        graphics::Point2::new(-size / 2.0, size / 2.0),
-        graphics::Point2::new(-3.0 * size / 4.0, size / 4.0),
-        graphics::Point2::new(-size - size / 2.0, size),
-        graphics::Point2::new(0.0, size),
-      ],
-    )
-    .clone();
+  // Player one keeps the original, untinted ship
+  let draw_param = graphics::DrawParam {
+    color: if player == 0 {
+      None
+    } else {
+      Some(player_color(player))
+    },
+    ..Default::default()
+  };
 
-  let draw_param = graphics::DrawParam::default();
+  let mesh_component = MeshComponent {
+    key: MeshKey::Player,
+    draw_param,
+  };
 
-  let mesh_component = MeshComponent { mesh, draw_param };
+  let controllable_component = ControllableComponent {
+    player,
+    ..Default::default()
+  };
+
+  let health_component = HealthComponent {
+    hp: 100.0,
+    max_hp: 100.0,
+  };
+
+  let camera_focus_component = CameraFocusComponent;
 
-  let controllable_component = ControllableComponent;
+  // Just behind where the wings meet the hull
+  let trail_component = TrailComponent {
+    attachments: vec![(-size, -size / 4.0), (-size, size / 4.0)],
+    frequency: Duration::from_millis(10),
+    elapsed: Duration::from_millis(0),
+  };
 
   let shooting_component = ShootingComponent {
     every: Duration::from_millis(250),
-    last_shot_at: Instant::now(),
+    since_last_shot: Duration::from_millis(0),
+  };
+
+  // Sparks out of the back of the ship, on top of the trail
+  let exhaust_component = ParticleEmitterComponent {
+    frequency: Duration::from_millis(40),
+    burst: 1,
+    offset: (-size, 0.0),
+    angle: std::f32::consts::PI,
+    spread: 0.4,
+    speed: (20.0, 60.0),
+    lifetime: Duration::from_millis(300),
+    size: ParticleCurve {
+      start: 2.0,
+      end: 0.0,
+      ease: Ease::In(Easing::Linear),
+    },
+    color: fading(trail_color()),
+    elapsed: Duration::from_millis(0),
   };
 
   let garbage_component = GarbageComponent::default();
 
+  let on_death_component = OnDeathComponent {
+    effects: vec![Effect::Spawn(Prefab::DeathExplosion)],
+  };
+
   let entity = LazyBuilder {
     entity: entities.create(),
     lazy: lazy,
@@ -107,88 +214,132 @@ pub fn create_player(
   .with(mesh_component)
   .with(rigid_body_component)
   .with(controllable_component)
+  .with(health_component)
+  .with(camera_focus_component)
+  .with(trail_component)
+  .with(exhaust_component)
   .with(shooting_component)
   .with(garbage_component)
+  .with(on_death_component)
   .build();
 
-  physics.bodies.insert(rigid_body_handle, entity);
+  physics.register_body(rigid_body_handle, entity, Some(ColliderType::Player));
 }
 
+// owner is the player that fired it, if any
 pub fn create_projectile(
   entities: &EntitiesRes,
   lazy: &LazyUpdate,
   settings: &GameSettings,
   physics: &mut PhysicsSim,
+  pools: &mut Pools,
+  owner: Option<usize>,
   position_component: PositionComponent,
 ) -> () {
+  let slot = match pools.acquire(PoolKind::Projectile) {
+    Some(slot) => slot,
+    None => return,
+  };
+
   let x = position_component.x;
   let y = position_component.y;
   let angle = position_component.angle;
   let velocity = 500.0;
 
-  let radius = 4.0;
-  let tolerance = 0.1;
-  let thickness = 1.0;
+  let radius = PROJECTILE_RADIUS;
 
+  let membership = match owner {
+    Some(player) => vec![
+      PLAYER_PROJECTILE_COLLISION_GROUP,
+      player_projectile_group(player),
+    ],
+    None => vec![PLAYER_PROJECTILE_COLLISION_GROUP],
+  };
+  // Friendly fire only spares the ship that fired
+  let blacklist = match owner {
+    Some(player) if settings.friendly_fire => player_body_group(player),
+    _ => PLAYER_BODY_COLLISION_GROUP,
+  };
   let collision_groups = CollisionGroups::new()
-    .with_membership(&[PLAYER_PROJECTILE_COLLISION_GROUP])
-    .with_blacklist(&[PLAYER_BODY_COLLISION_GROUP]);
-
-  let collider_desc = ColliderDesc::new(ShapeHandle::new(Ball::new(radius)))
-    .collision_groups(collision_groups)
-    .name(ColliderType::PlayerProjectile.to_string());;
-
-  collider_desc.build(&mut physics.world);
-
-  let rigid_body = RigidBodyDesc::new()
-    .collider(&collider_desc)
-    .position(Isometry2::new(Vector2::new(x, y), angle))
-    .status(BodyStatus::Dynamic)
-    .build(&mut physics.world);
-
-  rigid_body.set_linear_velocity(Vector2::new(angle.cos() * velocity, angle.sin() * velocity));
-
-  let rigid_body_component = RigidBodyComponent {
-    handle: rigid_body.handle(),
+    .with_membership(&membership)
+    .with_blacklist(&[blacklist]);
+
+  let isometry = Isometry2::new(Vector2::new(x, y), angle);
+  let linear_velocity = Vector2::new(angle.cos() * velocity, angle.sin() * velocity);
+
+  let (entity, handle) = match slot {
+    PoolSlot::Reuse(pooled) => {
+      let handle = pooled.body.unwrap();
+      physics.enable_body(
+        handle,
+        isometry,
+        Velocity::linear(linear_velocity.x, linear_velocity.y),
+        Some(collision_groups),
+      );
+      (pooled.entity, handle)
+    }
+    PoolSlot::Create => {
+      let collider_desc =
+        ColliderDesc::new(ShapeHandle::new(Ball::new(radius))).collision_groups(collision_groups);
+
+      let rigid_body = RigidBodyDesc::new()
+        .collider(&collider_desc)
+        .position(isometry)
+        .status(BodyStatus::Dynamic)
+        .build(&mut physics.world);
+
+      rigid_body.set_linear_velocity(linear_velocity);
+
+      let handle = rigid_body.handle();
+      let entity = entities.create();
+      physics.register_body(handle, entity, Some(ColliderType::PlayerProjectile));
+      (entity, handle)
+    }
   };
 
-  let mesh = graphics::MeshBuilder::new()
-    .circle(
-      graphics::DrawMode::Line(thickness),
-      graphics::Point2::origin(),
-      radius,
-      tolerance,
-    )
-    .clone();
+  let rigid_body_component = RigidBodyComponent { handle };
 
   let draw_param = graphics::DrawParam::default();
 
-  let mesh_component = MeshComponent { mesh, draw_param };
+  let mesh_component = MeshComponent {
+    key: MeshKey::Projectile,
+    draw_param,
+  };
 
   let garbage_component = GarbageComponent::default();
 
   let bounds_component = BoundsComponent {
     x_min: 0.0,
-    x_max: settings.width as f32,
+    x_max: settings.world_width as f32,
     y_min: 0.0,
-    y_max: settings.height as f32,
+    y_max: settings.world_height as f32,
   };
 
   let explode_bounds_component = ExplodeBoundsComponent;
 
-  let entity = LazyBuilder {
-    entity: entities.create(),
-    lazy: lazy,
-  }
-  .with(position_component)
-  .with(mesh_component)
-  .with(rigid_body_component)
-  .with(garbage_component)
-  .with(bounds_component)
-  .with(explode_bounds_component)
-  .build();
+  // Only shots that got a projectile out of the pool make a sound
+  let on_spawn_component = OnSpawnComponent {
+    effects: vec![Effect::Sound("shoot".to_string())],
+  };
+
+  let pooled_component = PooledComponent {
+    kind: PoolKind::Projectile,
+  };
 
-  physics.bodies.insert(rigid_body.handle(), entity);
+  let builder = LazyBuilder { entity, lazy: lazy }
+    .with(position_component)
+    .with(mesh_component)
+    .with(rigid_body_component)
+    .with(garbage_component)
+    .with(bounds_component)
+    .with(explode_bounds_component)
+    .with(on_spawn_component)
+    .with(pooled_component);
+
+  match owner {
+    Some(player) => builder.with(OwnerComponent { player }).build(),
+    None => builder.build(),
+  };
 }
 
 pub fn create_out_of_bounds_explosion(
@@ -196,18 +347,15 @@ pub fn create_out_of_bounds_explosion(
   lazy: &LazyUpdate,
   position_component: PositionComponent,
 ) -> () {
-  let line_width = 20.0;
-  let rect = graphics::Rect::new(-line_width / 2.0, -line_width / 2.0, line_width, line_width);
-  let mesh = graphics::MeshBuilder::new()
-    .polygon(graphics::DrawMode::Fill, &rect_to_polygon(rect))
-    .clone();
-
   let draw_param = graphics::DrawParam {
     color: Some(hp_color()),
     ..Default::default()
   };
 
-  let mesh_component = MeshComponent { mesh, draw_param };
+  let mesh_component = MeshComponent {
+    key: MeshKey::OutOfBoundsExplosion,
+    draw_param,
+  };
 
   let lifetime_component = LifetimeComponent {
     duration: Duration::from_millis(250),
@@ -221,6 +369,7 @@ pub fn create_out_of_bounds_explosion(
   }
   .with(position_component)
   .with(mesh_component)
+  .with(spark_burst(8, (40.0, 120.0), hp_color()))
   .with(lifetime_component)
   .with(garbage_component)
   .build();
@@ -230,77 +379,198 @@ pub fn create_death_explosion(
   entities: &EntitiesRes,
   lazy: &LazyUpdate,
   physics: &mut PhysicsSim,
+  pools: &mut Pools,
+  rng: &mut GameRng,
   x: f32,
   y: f32,
 ) -> () {
-  let mut rng = rand::thread_rng();
+  let rng = &mut rng.0;
 
   let count = rng.gen_range(4, 30);
 
   for _ in 0..count {
     let angle = rng.gen_range(0.0, 2.0 * std::f32::consts::PI);
-    let length = rng.gen_range(15.0, 30.0);
+    // Whole pixel lengths, so explosions share a handful of cached meshes
+    let length = rng.gen_range(15, 31) as f32;
     let velocity = rng.gen_range(100.0, 300.0);
-    let line_width = 2.0;
+
+    // Roll the line before checking the pool, so a full pool doesn't
+    // change what the rest of the game draws from the rng
+    let slot = match pools.acquire(PoolKind::ExplosionLine) {
+      Some(slot) => slot,
+      None => continue,
+    };
 
     let position_component = PositionComponent { x, y, angle };
 
-    let rigid_body = RigidBodyDesc::new()
-      .position(Isometry2::new(Vector2::new(x, y), angle))
-      .status(BodyStatus::Dynamic)
-      .build(&mut physics.world);
+    let isometry = Isometry2::new(Vector2::new(x, y), angle);
+    let linear_velocity = Vector2::new(angle.cos() * velocity, angle.sin() * velocity);
+
+    let (entity, handle) = match slot {
+      PoolSlot::Reuse(pooled) => {
+        let handle = pooled.body.unwrap();
+        physics.enable_body(
+          handle,
+          isometry,
+          Velocity::linear(linear_velocity.x, linear_velocity.y),
+          None,
+        );
+        (pooled.entity, handle)
+      }
+      PoolSlot::Create => {
+        let rigid_body = RigidBodyDesc::new()
+          .position(isometry)
+          .status(BodyStatus::Dynamic)
+          .build(&mut physics.world);
+
+        rigid_body.set_linear_velocity(linear_velocity);
+
+        (entities.create(), rigid_body.handle())
+      }
+    };
 
-    rigid_body.set_linear_velocity(Vector2::new(angle.cos() * velocity, angle.sin() * velocity));
+    let rigid_body_component = RigidBodyComponent { handle };
 
-    let rigid_body_component = RigidBodyComponent {
-      handle: rigid_body.handle(),
+    let key = MeshKey::ExplosionLine {
+      length: length as u32,
     };
 
-    let mesh = graphics::MeshBuilder::new()
-      .line(
-        &[
-          graphics::Point2::origin(),
-          graphics::Point2::new(length, 0.0),
-        ],
-        line_width,
-      )
-      .clone();
-
     let draw_param = graphics::DrawParam {
       color: Some(hp_color()),
       ..Default::default()
     };
 
-    let mesh_component = MeshComponent { mesh, draw_param };
+    let mesh_component = MeshComponent { key, draw_param };
 
-    let tween_component = TweenComponent {
-      tween_type: Tween::SizeTween {
-        ease: Ease::Cubic,
+    let tween_component = TweenComponent::new(TweenNode::tween(
+      Tween::SizeTween {
         starting: 1.0,
         ending: 0.0,
       },
-      elapsed: Duration::from_millis(0),
-      duration: Duration::from_millis(1000),
-    };
+      Ease::In(Easing::Cubic),
+      Duration::from_millis(1000),
+    ))
+    .on_complete(TweenAction::Garbage);
+
+    let garbage_component = GarbageComponent::default();
 
-    let lifetime_component = LifetimeComponent {
-      duration: Duration::from_millis(1000),
+    let pooled_component = PooledComponent {
+      kind: PoolKind::ExplosionLine,
     };
 
-    let garbage_component = GarbageComponent::default();
+    LazyBuilder { entity, lazy: lazy }
+      .with(position_component)
+      .with(rigid_body_component)
+      .with(mesh_component)
+      .with(tween_component)
+      .with(garbage_component)
+      .with(pooled_component)
+      .build();
+  }
 
-    let entity = LazyBuilder {
-      entity: entities.create(),
-      lazy: lazy,
-    }
+  // The lines get a cloud of sparks to go with them, from an entity that
+  // only lives long enough to emit it
+  LazyBuilder {
+    entity: entities.create(),
+    lazy: lazy,
+  }
+  .with(PositionComponent { x, y, angle: 0.0 })
+  .with(spark_burst(40, (60.0, 240.0), hp_color()))
+  .with(LifetimeComponent {
+    duration: Duration::from_millis(100),
+  })
+  .with(GarbageComponent::default())
+  .build();
+}
+
+// Emits `count` particles in every direction on its first tick, and not
+// again for longer than anything carrying it lives
+fn spark_burst(count: u32, speed: (f32, f32), color: graphics::Color) -> ParticleEmitterComponent {
+  let frequency = Duration::from_secs(60);
+
+  ParticleEmitterComponent {
+    frequency,
+    burst: count,
+    offset: (0.0, 0.0),
+    angle: 0.0,
+    spread: std::f32::consts::PI,
+    speed,
+    lifetime: Duration::from_millis(500),
+    size: ParticleCurve {
+      start: 3.0,
+      end: 0.0,
+      ease: Ease::Out(Easing::Quad),
+    },
+    color: fading(color),
+    elapsed: frequency,
+  }
+}
+
+fn fading(color: graphics::Color) -> ColorCurve {
+  ColorCurve {
+    start: color,
+    end: graphics::Color { a: 0.0, ..color },
+    ease: Ease::In(Easing::Linear),
+  }
+}
+
+pub fn create_trail_particle(
+  entities: &EntitiesRes,
+  lazy: &LazyUpdate,
+  pools: &mut Pools,
+  x: f32,
+  y: f32,
+  radius: f32,
+  color: graphics::Color,
+  lifetime: Duration,
+) -> () {
+  let entity = match pools.acquire(PoolKind::TrailParticle) {
+    Some(PoolSlot::Reuse(pooled)) => pooled.entity,
+    Some(PoolSlot::Create) => entities.create(),
+    None => return,
+  };
+
+  // Whole pixel radii, so trails share a handful of cached meshes
+  let radius = radius.round();
+
+  let position_component = PositionComponent { x, y, angle: 0.0 };
+
+  let key = MeshKey::TrailParticle {
+    radius: radius as u32,
+  };
+
+  let draw_param = graphics::DrawParam {
+    color: Some(color),
+    ..Default::default()
+  };
+
+  let mesh_component = MeshComponent { key, draw_param };
+
+  let tween_component = TweenComponent::new(TweenNode::tween(
+    Tween::SizeTween {
+      starting: 1.0,
+      ending: 0.0,
+    },
+    Ease::In(Easing::Cubic),
+    lifetime,
+  ));
+
+  let lifetime_component = LifetimeComponent { duration: lifetime };
+
+  let garbage_component = GarbageComponent::default();
+
+  let pooled_component = PooledComponent {
+    kind: PoolKind::TrailParticle,
+  };
+
+  LazyBuilder { entity, lazy: lazy }
     .with(position_component)
-    .with(rigid_body_component)
     .with(mesh_component)
     .with(tween_component)
     .with(lifetime_component)
     .with(garbage_component)
+    .with(pooled_component)
     .build();
-  }
 }
 
 pub fn create_ammo(
@@ -308,28 +578,45 @@ pub fn create_ammo(
   lazy: &LazyUpdate,
   settings: &GameSettings,
   physics: &mut PhysicsSim,
+  rng: &mut GameRng,
 ) -> () {
-  let mut rng = rand::thread_rng();
+  let offset = AMMO_BOUNDS_OFFSET;
+  let direction = if rng.0.gen() { -1.0 } else { 1.0 };
+  let x =
+    settings.world_width as f32 / 2.0 + direction * (settings.world_width as f32 / 2.0 + offset);
+  let y = rng
+    .0
+    .gen_range(offset, settings.world_height as f32 - offset);
+
+  create_ammo_at(entities, lazy, settings, physics, rng, x, y, -direction);
+}
+
+const AMMO_BOUNDS_OFFSET: f32 = 48.0;
 
-  let offset = 48.0;
-  let direction = if rand::random() { -1.0 } else { 1.0 };
-  let x = settings.width as f32 / 2.0 + direction * (settings.width as f32 / 2.0 + offset);
-  let y = rng.gen_range(offset, settings.height as f32 - offset);
+// direction is -1.0 to drift left, 1.0 to drift right
+pub fn create_ammo_at(
+  entities: &EntitiesRes,
+  lazy: &LazyUpdate,
+  settings: &GameSettings,
+  physics: &mut PhysicsSim,
+  rng: &mut GameRng,
+  x: f32,
+  y: f32,
+  direction: f32,
+) -> () {
+  let rng = &mut rng.0;
 
+  let offset = AMMO_BOUNDS_OFFSET;
   let angle = rng.gen_range(0.0, 2.0 * std::f32::consts::PI);
-  let velocity = -direction * rng.gen_range(30.0, 100.0);
-  let radius = 15.0;
-  let thickness = 3.0;
+  let velocity = direction * rng.gen_range(30.0, 100.0);
+  let radius = AMMO_RADIUS;
 
   let position_component = PositionComponent { x, y, angle };
 
   let collision_groups = CollisionGroups::new().with_membership(&[AMMO_BODY_COLLISION_GROUP]);
 
-  let collider_desc = ColliderDesc::new(ShapeHandle::new(Ball::new(radius)))
-    .collision_groups(collision_groups)
-    .name(ColliderType::Ammo.to_string());
-
-  collider_desc.build(&mut physics.world);
+  let collider_desc =
+    ColliderDesc::new(ShapeHandle::new(Ball::new(radius))).collision_groups(collision_groups);
 
   let rigid_body = RigidBodyDesc::new()
     .collider(&collider_desc)
@@ -344,25 +631,118 @@ pub fn create_ammo(
     handle: rigid_body.handle(),
   };
 
-  let rect = graphics::Rect::new(-radius, -radius, radius, radius);
-  let mesh = graphics::MeshBuilder::new()
-    .polygon(graphics::DrawMode::Line(thickness), &rect_to_polygon(rect))
-    .clone();
-
   let draw_param = graphics::DrawParam {
     color: Some(ammo_color()),
     ..Default::default()
   };
 
-  let mesh_component = MeshComponent { mesh, draw_param };
+  let mesh_component = MeshComponent {
+    key: MeshKey::Ammo,
+    draw_param,
+  };
 
   let garbage_component = GarbageComponent::default();
 
   let bounds_component = BoundsComponent {
     x_min: 0.0 - offset,
-    x_max: settings.width as f32 + offset,
+    x_max: settings.world_width as f32 + offset,
     y_min: 0.0,
-    y_max: settings.height as f32,
+    y_max: settings.world_height as f32,
+  };
+
+  // Shot down ammo explodes and scores; ammo drifting off screen does neither
+  let on_death_component = OnDeathComponent {
+    effects: vec![Effect::Spawn(Prefab::DeathExplosion), Effect::AddScore(1)],
+  };
+
+  let entity = LazyBuilder {
+    entity: entities.create(),
+    lazy: lazy,
+  }
+  .with(position_component)
+  .with(mesh_component)
+  .with(rigid_body_component)
+  .with(garbage_component)
+  .with(bounds_component)
+  .with(on_death_component)
+  .build();
+
+  physics.register_body(rigid_body.handle(), entity, Some(ColliderType::Ammo));
+}
+
+// Enemies may start this far outside the world before being culled
+const ENEMY_BOUNDS_OFFSET: f32 = 64.0;
+
+// A body whose behaviour comes entirely from the named script: its
+// update function steers it every tick, and its on_death function runs
+// when it's destroyed
+pub fn create_enemy(
+  entities: &EntitiesRes,
+  lazy: &LazyUpdate,
+  settings: &GameSettings,
+  physics: &mut PhysicsSim,
+  script: &str,
+  x: f32,
+  y: f32,
+  radius: f32,
+  hp: f32,
+  score: u32,
+) -> () {
+  let offset = ENEMY_BOUNDS_OFFSET;
+  let position_component = PositionComponent { x, y, angle: 0.0 };
+
+  let collision_groups = CollisionGroups::new().with_membership(&[ENEMY_BODY_COLLISION_GROUP]);
+
+  let collider_desc =
+    ColliderDesc::new(ShapeHandle::new(Ball::new(radius))).collision_groups(collision_groups);
+
+  let rigid_body = RigidBodyDesc::new()
+    .collider(&collider_desc)
+    .position(Isometry2::new(Vector2::new(x, y), 0.0))
+    .status(BodyStatus::Dynamic)
+    .build(&mut physics.world);
+
+  let rigid_body_component = RigidBodyComponent {
+    handle: rigid_body.handle(),
+  };
+
+  // Whole pixel radii, so enemies of a size share a mesh
+  let key = MeshKey::Enemy {
+    radius: radius.round() as u32,
+  };
+
+  let draw_param = graphics::DrawParam {
+    color: Some(enemy_color()),
+    ..Default::default()
+  };
+
+  let mesh_component = MeshComponent { key, draw_param };
+
+  let health_component = HealthComponent { hp, max_hp: hp };
+
+  let garbage_component = GarbageComponent::default();
+
+  let bounds_component = BoundsComponent {
+    x_min: 0.0 - offset,
+    x_max: settings.world_width as f32 + offset,
+    y_min: 0.0 - offset,
+    y_max: settings.world_height as f32 + offset,
+  };
+
+  let on_death_component = OnDeathComponent {
+    effects: vec![
+      Effect::Spawn(Prefab::DeathExplosion),
+      Effect::AddScore(score),
+      Effect::Script {
+        script: script.to_owned(),
+        function: "on_death".to_owned(),
+      },
+    ],
+  };
+
+  let script_component = ScriptComponent {
+    script: script.to_owned(),
+    vars: Default::default(),
   };
 
   let entity = LazyBuilder {
@@ -372,9 +752,12 @@ pub fn create_ammo(
   .with(position_component)
   .with(mesh_component)
   .with(rigid_body_component)
+  .with(health_component)
   .with(garbage_component)
   .with(bounds_component)
+  .with(on_death_component)
+  .with(script_component)
   .build();
 
-  physics.bodies.insert(rigid_body.handle(), entity);
+  physics.register_body(rigid_body.handle(), entity, Some(ColliderType::Enemy));
 }
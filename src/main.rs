@@ -13,99 +13,507 @@ use nphysics2d::solver::SignoriniModel;
 use shrev::EventChannel;
 use specs::prelude::*;
 use specs::{RunNow, World};
+use std::time::{Duration, Instant};
 
+mod audio;
 mod components;
+mod console;
 mod entities;
+mod net;
+mod replay;
 mod resources;
+mod scripting;
+mod settings;
+mod snapshot;
 mod systems;
 mod utils;
 
+use audio::{AudioBackend, GgezAudio, NullAudio};
 use components::*;
+use console::*;
+use net::Lockstep;
+use replay::*;
 use resources::*;
+use scripting::ScriptHost;
+use settings::*;
+use snapshot::RewindBuffer;
 use systems::*;
 use utils::*;
 
 #[macro_use]
 extern crate specs_derive;
 
+// Ticks advanced by one frame step press while shift is held
+const FRAME_STEP_MULTIPLE: u32 = 10;
+
+struct KeyboardLayout {
+  up: Keycode,
+  down: Keycode,
+  left: Keycode,
+  right: Keycode,
+  attack: Keycode,
+}
+
+// Indexed by player
+const KEYBOARD_LAYOUTS: [KeyboardLayout; MAX_PLAYERS] = [
+  KeyboardLayout {
+    up: Keycode::Up,
+    down: Keycode::Down,
+    left: Keycode::Left,
+    right: Keycode::Right,
+    attack: Keycode::Space,
+  },
+  KeyboardLayout {
+    up: Keycode::W,
+    down: Keycode::S,
+    left: Keycode::A,
+    right: Keycode::D,
+    attack: Keycode::F,
+  },
+];
+
 struct MainState<'a, 'b> {
   specs_world: World,
   dispatcher: Dispatcher<'a, 'b>,
   canvas: Canvas,
+  meshes: MeshCache,
+  font: graphics::Font,
+  console: Console,
+  replay: Option<Replay>,
+  recorder: Option<ReplayRecorder>,
+  rewind: Option<RewindBuffer>,
+  net: Option<Lockstep>,
+  audio: Box<dyn AudioBackend>,
+  // Last mouse position in window pixels
+  mouse: Option<(i32, i32)>,
 }
 
 impl<'a, 'b> MainState<'a, 'b> {
-  fn new(ctx: &mut Context, game_settings: GameSettings) -> GameResult<MainState<'a, 'b>> {
+  fn new(ctx: &mut Context, settings: &Settings) -> GameResult<MainState<'a, 'b>> {
     let canvas = Canvas::new(
       ctx,
-      game_settings.width / game_settings.scale,
-      game_settings.height / game_settings.scale,
+      settings.width / settings.scale,
+      settings.height / settings.scale,
       NumSamples::One,
     )
     .unwrap();
 
     graphics::set_default_filter(ctx, graphics::FilterMode::Nearest);
 
-    let mut specs_world = World::new();
+    let (specs_world, dispatcher) = create_world(settings);
+    let (replay, recorder) = open_replay(settings);
+    let font = graphics::Font::default_font()?;
+    let ggez_audio = if settings.mute {
+      None
+    } else {
+      GgezAudio::load(ctx)
+    };
+    let audio: Box<dyn AudioBackend> = match ggez_audio {
+      Some(audio) => Box::new(audio),
+      None => Box::new(NullAudio::default()),
+    };
 
-    let mut dispatcher = DispatcherBuilder::new()
-      .with(PhysicsSystem, "physics_system", &[])
-      .with(
-        CollisionSystem::default(),
-        "collision_system",
-        &["physics_system"],
-      )
-      .with(PositionSystem, "position_system", &["physics_system"])
-      .with(
-        ControllableSystem,
+    Ok(MainState {
+      specs_world,
+      dispatcher,
+      canvas,
+      meshes: MeshCache::new(),
+      font,
+      console: Console::load(),
+      replay,
+      recorder,
+      rewind: open_rewind(settings),
+      net: open_net(settings),
+      audio,
+      mouse: None,
+    })
+  }
+
+  // Runs one tick of the simulation. Returns false once the replay (if
+  // any) has run out of frames or the networked game is over.
+  fn tick(&mut self, dt: Duration) -> bool {
+    if !step_input(
+      &mut self.specs_world,
+      &mut self.replay,
+      &mut self.recorder,
+      &mut self.net,
+    ) {
+      return false;
+    }
+
+    set_update_time(&mut self.specs_world, dt);
+    run_tick(&mut self.specs_world, &mut self.dispatcher);
+    play_sounds(&self.specs_world, &mut *self.audio);
+
+    if !check_sync(&self.specs_world, &mut self.net) {
+      return false;
+    }
+
+    if let Some(rewind) = &mut self.rewind {
+      let dt = self.specs_world.read_resource::<UpdateTime>().0;
+      rewind.record(&self.specs_world, dt);
+    }
+
+    true
+  }
+
+  // Where keyboard and gamepad input goes: straight into the world's
+  // Inputs, or when networked into the input waiting to go to the peer
+  fn device_inputs(&mut self, f: impl FnOnce(&mut Inputs)) {
+    match &mut self.net {
+      Some(net) => f(&mut net.held),
+      None => f(&mut self.specs_world.write_resource::<Inputs>()),
+    }
+  }
+}
+
+fn create_world<'a, 'b>(settings: &Settings) -> (World, Dispatcher<'a, 'b>) {
+  // A random seed is worth knowing to play the run again. Either way it
+  // ends up in the run stats.
+  let seed = settings.seed.unwrap_or_else(|| {
+    let seed = rand::random();
+    eprintln!("Seed: {}", seed);
+    seed
+  });
+
+  let mut specs_world = World::new();
+  specs_world.add_resource(settings.game_settings(seed));
+  specs_world.add_resource(GameRng::from_seed(seed));
+  specs_world.add_resource(Score::default());
+  specs_world.add_resource(Pools::new(&settings.pools));
+  specs_world.add_resource(settings.audio.clone());
+  specs_world.add_resource(DebugOverlay::default());
+  specs_world.add_resource(TimeScale::default());
+  specs_world.add_resource(Profiler::new(settings.profile_trace.is_some()));
+  specs_world.add_resource(Inputs::new(settings.players));
+  specs_world.add_resource(RunStats::new(seed, entities::PLAYER_SHIP, settings.players));
+  specs_world.add_resource(Camera::new(
+    settings.world_width.unwrap_or(settings.width) as f32 / 2.0,
+    settings.world_height.unwrap_or(settings.height) as f32 / 2.0,
+  ));
+
+  let scripts = match &settings.scripts_dir {
+    Some(dir) => ScriptHost::load(dir).unwrap_or_else(|e| exit_with_error(&e)),
+    None => ScriptHost::empty(),
+  };
+
+  let mut dispatcher = DispatcherBuilder::new()
+    .with_profiled(PhysicsSystem::default(), "physics_system", &[])
+    .with_profiled(
+      CollisionSystem::default(),
+      "collision_system",
+      &["physics_system"],
+    )
+    .with_profiled(PositionSystem, "position_system", &["physics_system"])
+    .with_profiled(
+      ControllableSystem,
+      "controllable_system",
+      &["physics_system"],
+    )
+    .with_profiled(ShootingSystem, "shooting_system", &[])
+    .with_profiled(BoundsSystem, "bounds_system", &["position_system"])
+    .with_profiled(GarbageSystem, "garbage_system", &[])
+    .with_profiled(LifetimeSystem, "lifetime_system", &[])
+    .with_profiled(TweenSystem, "tween_system", &[])
+    .with_profiled(SpawnSystem, "spawn_system", &[])
+    .with_profiled(
+      DamageSystem::default(),
+      "damage_system",
+      &["collision_system"],
+    )
+    .with_profiled(
+      HitFlashSystem,
+      "hit_flash_system",
+      &["damage_system", "tween_system"],
+    )
+    .with_profiled(ParticleSystem, "particle_system", &["position_system"])
+    .with_profiled(
+      TrailSystem,
+      "trail_system",
+      &["position_system", "controllable_system"],
+    )
+    .with_profiled(
+      SpatialIndexSystem,
+      "spatial_index_system",
+      &["physics_system"],
+    )
+    .with_profiled(LifecycleSystem, "lifecycle_system", &[])
+    .with_profiled(
+      EffectSystem::default(),
+      "effect_system",
+      &[
+        "garbage_system",
+        "tween_system",
+        "lifecycle_system",
+        "bounds_system",
         "controllable_system",
-        &["physics_system"],
-      )
-      .with(ShootingSystem, "shooting_system", &[])
-      .with(BoundsSystem, "bounds_system", &["position_system"])
-      .with(GarbageSystem, "garbage_system", &[])
-      .with(LifetimeSystem, "lifetime_system", &[])
-      .with(TweenSystem, "tween_system", &[])
-      .with(SpawnSystem, "spawn_system", &[])
-      .build();
+      ],
+    )
+    .with_profiled(
+      ScriptSystem::new(scripts),
+      "script_system",
+      &["position_system", "effect_system", "spatial_index_system"],
+    )
+    .with_profiled(
+      CameraSystem::default(),
+      "camera_system",
+      &["position_system", "damage_system", "effect_system"],
+    )
+    .with_profiled(
+      AudioSystem::default(),
+      "audio_system",
+      &["shooting_system", "collision_system", "effect_system"],
+    )
+    .with_profiled(
+      StatsSystem::default(),
+      "stats_system",
+      &[
+        "shooting_system",
+        "collision_system",
+        "damage_system",
+        "effect_system",
+      ],
+    )
+    .build();
 
-    dispatcher.setup(&mut specs_world.res);
+  dispatcher.setup(&mut specs_world.res);
 
-    // I don't know what I'm doing. Is this needed?
-    {
-      let lazy = specs_world.read_resource::<LazyUpdate>();
-      let mut physics = specs_world.write_resource::<PhysicsSim>();
-      let game_settings = specs_world.read_resource::<GameSettings>();
+  // I don't know what I'm doing. Is this needed?
+  {
+    let lazy = specs_world.read_resource::<LazyUpdate>();
+    let mut physics = specs_world.write_resource::<PhysicsSim>();
+    let game_settings = specs_world.read_resource::<GameSettings>();
+    for player in 0..game_settings.players {
       entities::create_player(
-        ctx,
         &specs_world.entities(),
         &lazy,
         &game_settings,
         &mut physics,
+        player,
       );
     }
+  }
 
-    Ok(MainState {
-      specs_world,
-      dispatcher,
-      canvas,
-    })
+  if let Some(path) = &settings.load {
+    snapshot::load(&mut specs_world, path).unwrap_or_else(|e| exit_with_error(&e));
+  }
+
+  (specs_world, dispatcher)
+}
+
+fn open_replay(settings: &Settings) -> (Option<Replay>, Option<ReplayRecorder>) {
+  let replay = settings
+    .replay
+    .as_ref()
+    .map(|path| Replay::load(path).unwrap_or_else(|e| exit_with_error(&e)));
+  let recorder = settings
+    .record
+    .as_ref()
+    .map(|path| ReplayRecorder::create(path).unwrap_or_else(|e| exit_with_error(&e)));
+
+  (replay, recorder)
+}
+
+fn open_rewind(settings: &Settings) -> Option<RewindBuffer> {
+  if settings.rewind_seconds > 0.0 {
+    Some(RewindBuffer::new(settings.rewind_seconds))
+  } else {
+    None
+  }
+}
+
+fn open_net(settings: &Settings) -> Option<Lockstep> {
+  match (settings.net_bind, settings.net_peer) {
+    (Some(bind), Some(peer)) => Some(
+      Lockstep::open(
+        bind,
+        peer,
+        settings.net_player - 1,
+        settings.input_delay,
+        settings.seed.expect("Validated with the settings"),
+        &settings.stats_dir,
+      )
+      .unwrap_or_else(|e| exit_with_error(&e)),
+    ),
+    _ => None,
+  }
+}
+
+// Feeds the next replay frame into Inputs, swaps in the frame agreed with
+// the peer when networked, and records whatever Inputs end up being.
+// Returns false once the replay has run out of frames or the networked
+// game is over.
+fn step_input(
+  specs_world: &mut World,
+  replay: &mut Option<Replay>,
+  recorder: &mut Option<ReplayRecorder>,
+  net: &mut Option<Lockstep>,
+) -> bool {
+  let mut inputs = specs_world.write_resource::<Inputs>();
+
+  if let Some(replay) = replay {
+    let frame = match replay.next_frame() {
+      Some(frame) => frame,
+      None => return false,
+    };
+
+    // Players missing from the replay sit still
+    match net {
+      // Networked, the replay plays this instance's own player, from
+      // their column of a recording that has every player's input
+      Some(net) => net.held.0[0] = frame.0.get(net.player).cloned().unwrap_or_default(),
+      None => {
+        for (player, input) in inputs.0.iter_mut().enumerate() {
+          *input = frame.0.get(player).cloned().unwrap_or_default();
+        }
+      }
+    }
+  }
+
+  if let Some(net) = net {
+    match net.exchange() {
+      Ok(Some(frame)) => inputs.0 = frame,
+      Ok(None) => return false,
+      Err(e) => {
+        eprintln!("{}", e);
+        return false;
+      }
+    }
+  }
+
+  if let Some(recorder) = recorder {
+    recorder.record(&inputs);
+  }
+
+  true
+}
+
+// Compares the tick that just ran with the peer's. Returns false on a
+// desync, once the report has been written.
+fn check_sync(specs_world: &World, net: &mut Option<Lockstep>) -> bool {
+  if let Some(net) = net {
+    if let Err(e) = net.check(specs_world) {
+      eprintln!("{}", e);
+      return false;
+    }
+  }
+
+  true
+}
+
+// The gamepad drives the last player: the only one when playing alone, or
+// player two in co-op
+fn gamepad_input(inputs: &mut Inputs) -> &mut Input {
+  inputs
+    .0
+    .last_mut()
+    .expect("There's always at least one player")
+}
+
+// Sets whichever player's control the key is bound to
+fn set_key(inputs: &mut Inputs, keycode: Keycode, pressed: bool) {
+  for (layout, input) in KEYBOARD_LAYOUTS.iter().zip(inputs.0.iter_mut()) {
+    if keycode == layout.up {
+      input.up = pressed;
+    } else if keycode == layout.down {
+      input.down = pressed;
+    } else if keycode == layout.left {
+      input.left = pressed;
+    } else if keycode == layout.right {
+      input.right = pressed;
+    } else if keycode == layout.attack {
+      input.attack = pressed;
+    }
+  }
+}
+
+// The length of a tick when not following the frame rate: headless runs
+// and frame stepping
+fn fixed_tick() -> Duration {
+  Duration::from_nanos(1_000_000_000 / 60)
+}
+
+fn set_update_time(specs_world: &mut World, dt: Duration) {
+  let scale = specs_world.read_resource::<TimeScale>().0;
+  // In f64 nanoseconds, so a scale of 1.0 leaves dt exactly as it was
+  let nanos = dt.as_secs() * 1_000_000_000 + dt.subsec_nanos() as u64;
+  let scaled = (nanos as f64 * scale as f64) as u64;
+  specs_world.write_resource::<UpdateTime>().0 = Duration::from_nanos(scaled);
+}
+
+// Dispatches every system once, timing the whole tick alongside the
+// per-system timings
+fn run_tick(specs_world: &mut World, dispatcher: &mut Dispatcher) {
+  let start = Instant::now();
+  dispatcher.dispatch(&specs_world.res);
+  let dispatched = Instant::now();
+  specs_world.maintain();
+
+  let profiler = specs_world.read_resource::<Profiler>();
+  profiler.record("dispatch", start, dispatched.duration_since(start));
+  profiler.record("maintain", dispatched, dispatched.elapsed());
+}
+
+// Hands whatever the last tick wanted to hear to the audio backend
+fn play_sounds(specs_world: &World, audio: &mut dyn AudioBackend) {
+  for sound in specs_world.write_resource::<SoundQueue>().0.drain(..) {
+    audio.play(&sound);
+  }
+}
+
+// Everything that gets written out once a run is over
+fn finish_run(specs_world: &World, settings: &Settings, net: &mut Option<Lockstep>) {
+  if let Some(net) = net {
+    net.finish();
+  }
+
+  let stats = specs_world.read_resource::<RunStats>();
+  match stats.write(&settings.stats_dir) {
+    Ok(path) => println!("Wrote run stats to {}", path.display()),
+    Err(e) => eprintln!("{}", e),
+  }
+
+  if let Some(path) = &settings.profile_trace {
+    let profiler = specs_world.read_resource::<Profiler>();
+    match profiler.write_trace(path) {
+      Ok(()) => println!("Wrote profiler trace to {}", path.display()),
+      Err(e) => eprintln!("{}", e),
+    }
   }
 }
 
+fn exit_with_error(message: &str) -> ! {
+  eprintln!("{}", message);
+  std::process::exit(1);
+}
+
 impl<'a, 'b> event::EventHandler for MainState<'a, 'b> {
   fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-    let dt = timer::get_delta(ctx);
-
-    if timer::get_ticks(ctx) % 100 == 0 {
-      println!("Average FPS: {}", timer::get_fps(ctx));
+    // Peers have to agree on every tick's length, so a networked game
+    // ticks at a fixed rate
+    let dt = if self.net.is_some() {
+      fixed_tick()
+    } else {
+      timer::get_delta(ctx)
+    };
+
+    // The simulation is paused while scrubbing through the rewind buffer
+    if self
+      .rewind
+      .as_ref()
+      .map_or(false, |rewind| rewind.scrubbing())
+    {
+      return Ok(());
     }
 
-    self.specs_world.write_resource::<UpdateTime>().0 = dt;
-    self.specs_world.write_resource::<PhysicsSim>();
-    self.dispatcher.dispatch(&mut self.specs_world.res);
+    // Likewise while frame stepping, where ticks only run on a key press
+    if self.specs_world.read_resource::<DebugOverlay>().stepping {
+      return Ok(());
+    }
 
-    self.specs_world.maintain();
+    if !self.tick(dt) {
+      ctx.quit().expect("Should never fail");
+    }
 
     Ok(())
   }
@@ -117,10 +525,95 @@ impl<'a, 'b> event::EventHandler for MainState<'a, 'b> {
     graphics::set_background_color(ctx, utils::backround_color());
     graphics::clear(ctx);
 
+    let camera_transform = self.specs_world.read_resource::<Camera>().transform(
+      game_settings.width as f32 / game_settings.scale as f32,
+      game_settings.height as f32 / game_settings.scale as f32,
+    );
+    graphics::push_transform(ctx, Some(camera_transform));
+    graphics::apply_transformations(ctx)?;
+
     {
-      let mut rs = RenderingSystem { ctx };
+      let mut rs = Profiled::new(
+        "rendering_system",
+        RenderingSystem {
+          ctx,
+          meshes: &mut self.meshes,
+        },
+      );
       rs.run_now(&self.specs_world.res);
     }
+
+    {
+      let mut debug_shapes = DebugShapesSystem { ctx };
+      debug_shapes.run_now(&self.specs_world.res);
+    }
+
+    graphics::pop_transform(ctx);
+    graphics::apply_transformations(ctx)?;
+
+    {
+      let mut hud = HudSystem {
+        ctx,
+        font: &self.font,
+        width: game_settings.width as f32 / game_settings.scale as f32,
+        height: game_settings.height as f32 / game_settings.scale as f32,
+      };
+      hud.run_now(&self.specs_world.res);
+    }
+
+    {
+      let mut debug_stats = DebugStatsSystem {
+        ctx,
+        font: &self.font,
+      };
+      debug_stats.run_now(&self.specs_world.res);
+    }
+
+    {
+      let width = game_settings.width as f32 / game_settings.scale as f32;
+      let height = game_settings.height as f32 / game_settings.scale as f32;
+      let scale = game_settings.scale as f32;
+      let cursor = self.mouse.map(|(x, y)| {
+        self.specs_world.read_resource::<Camera>().screen_to_world(
+          x as f32 / scale,
+          y as f32 / scale,
+          width,
+          height,
+        )
+      });
+      self.specs_world.write_resource::<DebugOverlay>().cursor = cursor;
+
+      let mut inspector = DebugInspectorSystem {
+        ctx,
+        font: &self.font,
+        height,
+      };
+      inspector.run_now(&self.specs_world.res);
+    }
+
+    {
+      let mut profiler_overlay = ProfilerOverlaySystem {
+        ctx,
+        font: &self.font,
+        width: game_settings.width as f32 / game_settings.scale as f32,
+      };
+      profiler_overlay.run_now(&self.specs_world.res);
+    }
+
+    if let Some(rewind) = &self.rewind {
+      rewind.draw(
+        ctx,
+        &self.font,
+        game_settings.height as f32 / game_settings.scale as f32,
+      )?;
+    }
+
+    self.console.draw(
+      ctx,
+      &self.font,
+      game_settings.width as f32 / game_settings.scale as f32,
+    )?;
+
     graphics::set_canvas(ctx, None);
 
     graphics::draw_ex(
@@ -145,58 +638,219 @@ impl<'a, 'b> event::EventHandler for MainState<'a, 'b> {
     _keymod: Mod,
     repeat: bool,
   ) {
-    let mut input = self.specs_world.write_resource::<Input>();
-
     if !repeat {
-      match keycode {
-        Keycode::Left => input.left = false,
-        Keycode::Right => input.right = false,
-        Keycode::Up => input.up = false,
-        Keycode::Down => input.down = false,
-        Keycode::Space => input.attack = false,
-        _ => (),
-      }
+      self.device_inputs(|inputs| set_key(inputs, keycode, false));
     }
   }
 
-  fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, _keymod: Mod, repeat: bool) {
-    let mut input = self.specs_world.write_resource::<Input>();
+  fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, keymod: Mod, repeat: bool) {
+    if keycode == Keycode::Backquote && !repeat {
+      self.console.toggle();
+      // Nothing stays held down while the console has the keyboard
+      self.device_inputs(|inputs| inputs.release_all());
+      return;
+    }
 
-    if !repeat {
+    if self.console.open {
       match keycode {
-        Keycode::Left => input.left = true,
-        Keycode::Right => input.right = true,
-        Keycode::Up => input.up = true,
-        Keycode::Down => input.down = true,
-        Keycode::Space => input.attack = true,
+        Keycode::Return => self
+          .console
+          .submit(&mut self.specs_world, self.net.is_some()),
+        Keycode::Backspace => self.console.backspace(),
+        Keycode::Up => self.console.history_up(),
+        Keycode::Down => self.console.history_down(),
+        Keycode::Escape => self.console.toggle(),
         _ => (),
       }
+      return;
+    }
+
+    if let Some(rewind) = &mut self.rewind {
+      if keycode == Keycode::F3 && !repeat {
+        if rewind.scrubbing() {
+          rewind.resume();
+        } else {
+          rewind.start();
+          self.specs_world.write_resource::<Inputs>().release_all();
+        }
+        return;
+      }
+
+      if rewind.scrubbing() {
+        let ticks = match keycode {
+          Keycode::Left => -1,
+          Keycode::Right => 1,
+          _ => 0,
+        };
+        if ticks != 0 {
+          rewind
+            .step(&mut self.specs_world, ticks)
+            .unwrap_or_else(|e| eprintln!("{}", e));
+        }
+        return;
+      }
+    }
+
+    // The peer can't be paused along with us
+    if keycode == Keycode::F4 && !repeat && self.net.is_none() {
+      let mut overlay = self.specs_world.write_resource::<DebugOverlay>();
+      overlay.stepping = !overlay.stepping;
+      return;
+    }
+
+    if keycode == Keycode::Period && self.specs_world.read_resource::<DebugOverlay>().stepping {
+      let ticks = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+        FRAME_STEP_MULTIPLE
+      } else {
+        1
+      };
+      for _ in 0..ticks {
+        if !self.tick(fixed_tick()) {
+          ctx.quit().expect("Should never fail");
+          break;
+        }
+      }
+      return;
+    }
+
+    if !repeat {
+      self.device_inputs(|inputs| set_key(inputs, keycode, true));
     }
 
     if keycode == Keycode::Q {
       ctx.quit().expect("Should never fail");
     }
+
+    if keycode == Keycode::F1 && !repeat {
+      let mut overlay = self.specs_world.write_resource::<DebugOverlay>();
+      overlay.enabled = !overlay.enabled;
+    }
+
+    if keycode == Keycode::F2 && !repeat {
+      let mut overlay = self.specs_world.write_resource::<DebugOverlay>();
+      overlay.profiler = !overlay.profiler;
+    }
+  }
+
+  fn mouse_motion_event(
+    &mut self,
+    _ctx: &mut Context,
+    _state: MouseState,
+    x: i32,
+    y: i32,
+    _xrel: i32,
+    _yrel: i32,
+  ) {
+    self.mouse = Some((x, y));
+  }
+
+  fn text_input_event(&mut self, _ctx: &mut Context, text: String) {
+    if self.console.open {
+      self.console.type_text(&text);
+    }
+  }
+
+  fn controller_button_down_event(&mut self, _ctx: &mut Context, btn: Button, _instance_id: i32) {
+    self.device_inputs(|inputs| {
+      let input = gamepad_input(inputs);
+
+      match btn {
+        Button::DPadLeft => input.left = true,
+        Button::DPadRight => input.right = true,
+        Button::DPadUp => input.up = true,
+        Button::DPadDown => input.down = true,
+        Button::A => input.attack = true,
+        _ => (),
+      }
+    });
+  }
+
+  fn controller_button_up_event(&mut self, _ctx: &mut Context, btn: Button, _instance_id: i32) {
+    self.device_inputs(|inputs| {
+      let input = gamepad_input(inputs);
+
+      match btn {
+        Button::DPadLeft => input.left = false,
+        Button::DPadRight => input.right = false,
+        Button::DPadUp => input.up = false,
+        Button::DPadDown => input.down = false,
+        Button::A => input.attack = false,
+        _ => (),
+      }
+    });
+  }
+
+  fn controller_axis_event(
+    &mut self,
+    _ctx: &mut Context,
+    axis: Axis,
+    value: i16,
+    _instance_id: i32,
+  ) {
+    let value = normalize_axis(value);
+
+    self.device_inputs(|inputs| {
+      let input = gamepad_input(inputs);
+
+      match axis {
+        Axis::LeftX => input.turn_axis = value,
+        Axis::TriggerRight => input.boost_axis = value.max(0.0),
+        Axis::TriggerLeft => input.brake_axis = value.max(0.0),
+        _ => (),
+      }
+    });
+  }
+}
+
+// Runs the simulation at a fixed 60 ticks per second without opening a
+// window. Stops after --ticks, when the replay runs out of frames, or
+// when the networked game is over.
+fn run_headless(settings: &Settings) {
+  let (mut specs_world, mut dispatcher) = create_world(settings);
+  let (mut replay, mut recorder) = open_replay(settings);
+  let mut net = open_net(settings);
+  let mut audio = NullAudio::default();
+  let dt = fixed_tick();
+  let mut ticks = 0;
+  let mut desynced = false;
+
+  while settings.ticks.map_or(true, |limit| ticks < limit)
+    && step_input(&mut specs_world, &mut replay, &mut recorder, &mut net)
+  {
+    ticks += 1;
+    set_update_time(&mut specs_world, dt);
+    run_tick(&mut specs_world, &mut dispatcher);
+    play_sounds(&specs_world, &mut audio);
+
+    if !check_sync(&specs_world, &mut net) {
+      desynced = true;
+      break;
+    }
+  }
+
+  finish_run(&specs_world, settings, &mut net);
+
+  if let Some(path) = &settings.sound_log {
+    match audio.write_log(path) {
+      Ok(()) => println!("Wrote sound log to {}", path.display()),
+      Err(e) => eprintln!("{}", e),
+    }
+  }
+
+  // So scripts and tests running peers can tell a desync from a clean end
+  if desynced {
+    std::process::exit(1);
   }
 }
 
 fn main() {
-  let game_settings = GameSettings {
-    width: 1920,
-    height: 1080,
-    scale: 1,
-  };
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let settings = Settings::load(&args).unwrap_or_else(|e| exit_with_error(&e));
 
-  let window_mode = WindowMode {
-    width: game_settings.width,
-    height: game_settings.height,
-    borderless: false,
-    fullscreen_type: ggez::conf::FullscreenType::Off,
-    vsync: true,
-    min_width: 0,
-    min_height: 0,
-    max_width: 0,
-    max_height: 0,
-  };
+  if settings.headless {
+    run_headless(&settings);
+    return;
+  }
 
   let window_setup = WindowSetup {
     title: "BYTEPATH-rs".to_owned(),
@@ -205,11 +859,12 @@ fn main() {
   };
 
   let ctx = &mut ggez::ContextBuilder::new("bytepath", "ggez")
-    .window_mode(window_mode)
+    .window_mode(settings.window_mode())
     .window_setup(window_setup)
     .build()
     .unwrap();
 
-  let mut state = MainState::new(ctx, game_settings).unwrap();
+  let mut state = MainState::new(ctx, &settings).unwrap();
   event::run(ctx, &mut state).unwrap();
+  finish_run(&state.specs_world, &settings, &mut state.net);
 }
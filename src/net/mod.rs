@@ -0,0 +1,496 @@
+use crate::components::*;
+use crate::resources::*;
+use crate::snapshot::{self, Snapshot};
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, ErrorKind};
+use std::net::{SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long to wait for the peer to show up, and how long it may then go
+// quiet before the game is abandoned
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+// Unacknowledged frames are sent again this often while waiting
+const RESEND_EVERY: Duration = Duration::from_millis(20);
+// How long to keep resending after stopping, so the peer can catch up
+const LINGER: Duration = Duration::from_secs(1);
+// World hashes for this many recent ticks ride along on every packet
+const HASH_HISTORY: u64 = 32;
+const MAX_PACKET: usize = 64 * 1024;
+
+// Everything one peer tells the other, sent every tick and whenever it's
+// waiting. UDP may drop or reorder packets, so each one repeats whatever
+// hasn't been acknowledged yet.
+#[derive(Serialize, Deserialize)]
+struct Packet {
+  seed: u64,
+  player: usize,
+  // The sender's input for each tick the receiver hasn't acknowledged
+  frames: Vec<(u64, Input)>,
+  // Every one of the receiver's frames before this tick has arrived
+  ack: u64,
+  hashes: Vec<(u64, u64)>,
+  // Set once the sender has stopped, to the first tick it didn't run
+  finished: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct DesyncReport {
+  tick: u64,
+  player: usize,
+  seed: u64,
+  local_hash: String,
+  peer_hash: String,
+  // The snapshot is taken when the desync is noticed, which can be a few
+  // ticks after it happened
+  captured_at: u64,
+  snapshot: Snapshot,
+}
+
+// Two instances each simulating the whole game and swapping only their
+// own player's input. A tick runs once both players' inputs for it are
+// known. Input is scheduled `delay` ticks ahead so it usually arrives
+// before it's needed; the first `delay` ticks run with no input at all.
+//
+// Both sides hash their world after every tick and compare hashes, so a
+// simulation that drifts apart is caught and written up as a report.
+pub struct Lockstep {
+  socket: UdpSocket,
+  pub player: usize,
+  // This instance's own controls, fed by the keyboard, gamepad or replay.
+  // A single entry so it can be driven like a local game.
+  pub held: Inputs,
+  delay: u64,
+  seed: u64,
+  report_dir: PathBuf,
+  // The next tick to run
+  tick: u64,
+  // Our inputs from the oldest one the peer hasn't acknowledged
+  local: BTreeMap<u64, Input>,
+  remote: BTreeMap<u64, Input>,
+  remote_player: Option<usize>,
+  // Every frame before these ticks has arrived at the peer, and from it
+  peer_ack: u64,
+  received_until: u64,
+  local_hashes: BTreeMap<u64, u64>,
+  remote_hashes: BTreeMap<u64, u64>,
+  peer_finished: Option<u64>,
+  last_heard: Option<Instant>,
+}
+
+impl Lockstep {
+  pub fn open(
+    bind: SocketAddr,
+    peer: SocketAddr,
+    player: usize,
+    delay: u32,
+    seed: u64,
+    report_dir: &Path,
+  ) -> Result<Lockstep, String> {
+    let socket = UdpSocket::bind(bind).map_err(|e| format!("Unable to bind to {}: {}", bind, e))?;
+    socket
+      .connect(peer)
+      .map_err(|e| format!("Unable to reach peer {}: {}", peer, e))?;
+    socket
+      .set_nonblocking(true)
+      .map_err(|e| format!("Unable to set up socket: {}", e))?;
+
+    let delay = u64::from(delay);
+
+    Ok(Lockstep {
+      socket,
+      player,
+      held: Inputs::new(1),
+      delay,
+      seed,
+      report_dir: report_dir.to_owned(),
+      tick: 0,
+      local: BTreeMap::new(),
+      remote: BTreeMap::new(),
+      remote_player: None,
+      peer_ack: delay,
+      received_until: delay,
+      local_hashes: BTreeMap::new(),
+      remote_hashes: BTreeMap::new(),
+      peer_finished: None,
+      last_heard: None,
+    })
+  }
+
+  // Sends our held input off for a later tick and waits for the peer's
+  // input for this one. Returns every player's input for the tick, or
+  // None once the peer has stopped.
+  pub fn exchange(&mut self) -> Result<Option<Vec<Input>>, String> {
+    self
+      .local
+      .insert(self.tick + self.delay, self.held.0[0].clone());
+    self.send(None)?;
+
+    let started = Instant::now();
+    let mut last_sent = started;
+
+    loop {
+      self.drain()?;
+
+      if self
+        .peer_finished
+        .map_or(false, |finished| self.tick >= finished)
+      {
+        return Ok(None);
+      }
+
+      if self.tick < self.delay || self.received_until > self.tick {
+        break;
+      }
+
+      let (since, timeout) = match self.last_heard {
+        Some(last_heard) => (last_heard, PEER_TIMEOUT),
+        None => (started, CONNECT_TIMEOUT),
+      };
+      if since.elapsed() > timeout {
+        return Err(format!(
+          "Lost contact with the peer waiting for tick {}",
+          self.tick
+        ));
+      }
+
+      if last_sent.elapsed() >= RESEND_EVERY {
+        self.send(None)?;
+        last_sent = Instant::now();
+      }
+
+      thread::sleep(Duration::from_millis(1));
+    }
+
+    let mut frame = vec![Input::default(); MAX_PLAYERS];
+    if self.tick >= self.delay {
+      frame[self.player] = self.local[&self.tick].clone();
+      if let Some(remote_player) = self.remote_player {
+        frame[remote_player] = self.remote.remove(&self.tick).unwrap();
+      }
+    }
+
+    self.tick += 1;
+
+    let keep_from = self.peer_ack.min(self.tick);
+    self.local = self.local.split_off(&keep_from);
+
+    Ok(Some(frame))
+  }
+
+  // Call after each tick has run. Hashes the world and compares it with
+  // the peer's hash for the same tick, writing a report on a mismatch.
+  pub fn check(&mut self, world: &World) -> Result<(), String> {
+    let tick = self.tick - 1;
+    self.local_hashes.insert(tick, world_hash(world));
+
+    let oldest = tick.saturating_sub(HASH_HISTORY);
+    self.local_hashes = self.local_hashes.split_off(&oldest);
+    self.remote_hashes = self.remote_hashes.split_off(&oldest);
+
+    let mismatch =
+      self
+        .local_hashes
+        .iter()
+        .find_map(|(tick, local)| match self.remote_hashes.get(tick) {
+          Some(remote) if remote != local => Some((*tick, *local, *remote)),
+          _ => None,
+        });
+
+    match mismatch {
+      Some((tick, local, remote)) => {
+        let path = self.write_report(world, tick, local, remote)?;
+        Err(format!(
+          "Desync at tick {}: our hash {:016x}, peer's {:016x}. Wrote report to {}",
+          tick,
+          local,
+          remote,
+          path.display()
+        ))
+      }
+      None => Ok(()),
+    }
+  }
+
+  // Tells the peer we've stopped and keeps resending for a moment, until
+  // it has stopped at the same tick too
+  pub fn finish(&mut self) {
+    let finished = self.tick;
+    let started = Instant::now();
+
+    while started.elapsed() < LINGER {
+      if self.send(Some(finished)).is_err() || self.drain().is_err() {
+        return;
+      }
+      if self.peer_finished.is_some() {
+        return;
+      }
+      thread::sleep(RESEND_EVERY);
+    }
+  }
+
+  fn send(&self, finished: Option<u64>) -> Result<(), String> {
+    let packet = Packet {
+      seed: self.seed,
+      player: self.player,
+      frames: self
+        .local
+        .range(self.peer_ack..)
+        .map(|(tick, input)| (*tick, input.clone()))
+        .collect(),
+      ack: self.received_until,
+      hashes: self
+        .local_hashes
+        .iter()
+        .map(|(tick, hash)| (*tick, *hash))
+        .collect(),
+      finished,
+    };
+    let bytes = serde_json::to_vec(&packet).unwrap();
+
+    match self.socket.send(&bytes) {
+      Ok(_) => Ok(()),
+      // Nobody listening yet, the next resend will try again
+      Err(ref e) if e.kind() == ErrorKind::ConnectionRefused => Ok(()),
+      Err(e) => Err(format!("Unable to send to peer: {}", e)),
+    }
+  }
+
+  // Handles every packet that has arrived without waiting for more
+  fn drain(&mut self) -> Result<(), String> {
+    let mut buf = vec![0; MAX_PACKET];
+
+    loop {
+      let len = match self.socket.recv(&mut buf) {
+        Ok(len) => len,
+        Err(ref e)
+          if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::ConnectionRefused =>
+        {
+          return Ok(())
+        }
+        Err(e) => return Err(format!("Unable to receive from peer: {}", e)),
+      };
+
+      // Anything that isn't one of our packets is ignored
+      if let Ok(packet) = serde_json::from_slice::<Packet>(&buf[..len]) {
+        self.receive(packet)?;
+      }
+    }
+  }
+
+  fn receive(&mut self, packet: Packet) -> Result<(), String> {
+    if packet.seed != self.seed {
+      return Err(format!(
+        "The peer is playing seed {}, we're playing {}",
+        packet.seed, self.seed
+      ));
+    }
+    if packet.player >= MAX_PLAYERS {
+      return Err(format!(
+        "The peer claims to be player {}",
+        packet.player + 1
+      ));
+    }
+    if packet.player == self.player {
+      return Err(format!(
+        "The peer is also player {}, use --net-player to pick different players",
+        packet.player + 1
+      ));
+    }
+
+    self.last_heard = Some(Instant::now());
+    self.remote_player = Some(packet.player);
+    self.peer_ack = self.peer_ack.max(packet.ack);
+    if packet.finished.is_some() {
+      self.peer_finished = packet.finished;
+    }
+
+    for (tick, input) in packet.frames {
+      if tick >= self.received_until {
+        self.remote.insert(tick, input);
+      }
+    }
+    while self.remote.contains_key(&self.received_until) {
+      self.received_until += 1;
+    }
+
+    self.remote_hashes.extend(packet.hashes);
+
+    Ok(())
+  }
+
+  // Writes dir/desync-<seed>-<tick>-p<player>.json. Each peer writes its
+  // own, so diffing the two shows where the worlds differ.
+  fn write_report(
+    &self,
+    world: &World,
+    tick: u64,
+    local_hash: u64,
+    peer_hash: u64,
+  ) -> Result<PathBuf, String> {
+    let dir = &self.report_dir;
+    fs::create_dir_all(dir)
+      .map_err(|e| format!("Unable to create report directory {}: {}", dir.display(), e))?;
+
+    let report = DesyncReport {
+      tick,
+      player: self.player,
+      seed: self.seed,
+      local_hash: format!("{:016x}", local_hash),
+      peer_hash: format!("{:016x}", peer_hash),
+      captured_at: self.tick - 1,
+      snapshot: snapshot::capture(world)?,
+    };
+
+    let path = dir.join(format!(
+      "desync-{}-{}-p{}.json",
+      self.seed,
+      tick,
+      self.player + 1
+    ));
+    let file = File::create(&path)
+      .map_err(|e| format!("Unable to write desync report {}: {}", path.display(), e))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &report)
+      .map_err(|e| format!("Unable to write desync report {}: {}", path.display(), e))?;
+
+    Ok(path)
+  }
+}
+
+// Everything that decides how the game plays out from here: entities,
+// their bodies, health, timers and script vars, the ammo spawner, the
+// score and the random number generator. Cosmetic state like particles
+// and camera shake is left out.
+pub fn world_hash(world: &World) -> u64 {
+  let mut hasher = Fnv1a::new();
+  let physics = world.read_resource::<PhysicsSim>();
+  let position = world.read_storage::<PositionComponent>();
+  let rigid_body = world.read_storage::<RigidBodyComponent>();
+  let health = world.read_storage::<HealthComponent>();
+  let shooting = world.read_storage::<ShootingComponent>();
+  let lifetime = world.read_storage::<LifetimeComponent>();
+  let garbage = world.read_storage::<GarbageComponent>();
+  let script = world.read_storage::<ScriptComponent>();
+
+  for (entity, position, rigid_body, health, shooting, lifetime, garbage, script) in (
+    &world.entities(),
+    position.maybe(),
+    rigid_body.maybe(),
+    health.maybe(),
+    shooting.maybe(),
+    lifetime.maybe(),
+    garbage.maybe(),
+    script.maybe(),
+  )
+    .join()
+  {
+    hasher.u32(entity.id());
+
+    if let Some(position) = position {
+      hasher.floats(&[position.x, position.y, position.angle]);
+    }
+
+    let body = rigid_body.and_then(|rigid_body| physics.world.rigid_body(rigid_body.handle));
+    if let Some(body) = body {
+      let velocity = body.velocity();
+      hasher.floats(&[velocity.linear.x, velocity.linear.y, velocity.angular]);
+    }
+
+    if let Some(health) = health {
+      hasher.floats(&[health.hp]);
+    }
+
+    if let Some(shooting) = shooting {
+      hasher.duration(shooting.since_last_shot);
+    }
+
+    if let Some(lifetime) = lifetime {
+      hasher.duration(lifetime.duration);
+    }
+
+    if let Some(garbage) = garbage {
+      hasher.bytes(&[garbage.is_alive as u8, garbage.culled as u8]);
+    }
+
+    if let Some(script) = script {
+      hasher.str(&script.script);
+      hasher.vars(&script.vars);
+    }
+  }
+
+  let spawn_info = world.read_resource::<SpawnInfo>();
+  hasher.duration(spawn_info.ammo_since_last);
+  hasher.u32(spawn_info.ammo_count);
+
+  hasher.vars(&world.read_resource::<ScriptVars>().0);
+  hasher.u32(world.read_resource::<Score>().0);
+  // The generator's state is private, but it serialises
+  hasher.str(&serde_json::to_string(&*world.read_resource::<GameRng>()).unwrap());
+
+  hasher.finish()
+}
+
+// 64-bit FNV-1a, written out here rather than taken from std, whose
+// hasher may change between Rust releases and make peers built with
+// different toolchains disagree. Everything goes in as little-endian
+// bytes, so the platform doesn't matter either.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+  const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+  const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+  fn new() -> Fnv1a {
+    Fnv1a(Fnv1a::OFFSET_BASIS)
+  }
+
+  fn bytes(&mut self, bytes: &[u8]) {
+    for byte in bytes {
+      self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(Fnv1a::PRIME);
+    }
+  }
+
+  fn u32(&mut self, value: u32) {
+    self.bytes(&value.to_le_bytes());
+  }
+
+  fn u64(&mut self, value: u64) {
+    self.bytes(&value.to_le_bytes());
+  }
+
+  // By bit pattern, so any difference at all shows up
+  fn floats(&mut self, values: &[f32]) {
+    for value in values {
+      self.u32(value.to_bits());
+    }
+  }
+
+  // Length first, so "ab" + "c" and "a" + "bc" differ
+  fn str(&mut self, value: &str) {
+    self.u64(value.len() as u64);
+    self.bytes(value.as_bytes());
+  }
+
+  fn duration(&mut self, value: Duration) {
+    self.u64(value.as_secs());
+    self.u32(value.subsec_nanos());
+  }
+
+  // In key order, which a BTreeMap always iterates in
+  fn vars(&mut self, vars: &BTreeMap<String, f64>) {
+    for (name, value) in vars {
+      self.str(name);
+      self.u64(value.to_bits());
+    }
+  }
+
+  fn finish(&self) -> u64 {
+    self.0
+  }
+}
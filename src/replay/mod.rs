@@ -0,0 +1,58 @@
+use crate::resources::*;
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Replays are JSON lines, one per tick, each an array with every local
+// player's Input
+pub struct Replay {
+  frames: Vec<Inputs>,
+  cursor: usize,
+}
+
+impl Replay {
+  pub fn load(path: &Path) -> Result<Replay, String> {
+    let contents = fs::read_to_string(path)
+      .map_err(|e| format!("Unable to read replay {}: {}", path.display(), e))?;
+
+    let frames = contents
+      .lines()
+      .enumerate()
+      .filter(|(_, line)| !line.trim().is_empty())
+      .map(|(i, line)| {
+        serde_json::from_str(line)
+          .map(Inputs)
+          .map_err(|e| format!("Invalid replay {} line {}: {}", path.display(), i + 1, e))
+      })
+      .collect::<Result<Vec<Inputs>, String>>()?;
+
+    Ok(Replay { frames, cursor: 0 })
+  }
+
+  pub fn next_frame(&mut self) -> Option<&Inputs> {
+    let frame = self.frames.get(self.cursor);
+    self.cursor += 1;
+    frame
+  }
+}
+
+pub struct ReplayRecorder {
+  writer: BufWriter<File>,
+}
+
+impl ReplayRecorder {
+  pub fn create(path: &Path) -> Result<ReplayRecorder, String> {
+    let file = File::create(path)
+      .map_err(|e| format!("Unable to create replay {}: {}", path.display(), e))?;
+
+    Ok(ReplayRecorder {
+      writer: BufWriter::new(file),
+    })
+  }
+
+  pub fn record(&mut self, inputs: &Inputs) {
+    serde_json::to_writer(&mut self.writer, &inputs.0).expect("Unable to write replay frame");
+    writeln!(self.writer).expect("Unable to write replay frame");
+  }
+}
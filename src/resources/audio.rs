@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+
+// Each sound plays at its own volume times its category's, times master
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundCategory {
+  Weapons,
+  Pickups,
+  Impacts,
+  Explosions,
+}
+
+impl FromStr for SoundCategory {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<SoundCategory, String> {
+    match name {
+      "weapons" => Ok(SoundCategory::Weapons),
+      "pickups" => Ok(SoundCategory::Pickups),
+      "impacts" => Ok(SoundCategory::Impacts),
+      "explosions" => Ok(SoundCategory::Explosions),
+      _ => Err(format!("unknown sound category '{}'", name)),
+    }
+  }
+}
+
+pub struct SoundDef {
+  pub name: &'static str,
+  // Relative to the resources directory, like every ggez path
+  pub path: &'static str,
+  pub category: SoundCategory,
+  pub volume: f32,
+  // Each play is pitched up or down by up to this fraction, so rapid
+  // repeats don't sound mechanical
+  pub pitch_variance: f32,
+  // Plays past this many at once are dropped
+  pub max_concurrent: usize,
+  // Roughly how long the file is, to know when a play has finished
+  pub length: Duration,
+}
+
+pub const SOUNDS: &[SoundDef] = &[
+  SoundDef {
+    name: "shoot",
+    path: "/sounds/shoot.ogg",
+    category: SoundCategory::Weapons,
+    volume: 0.4,
+    pitch_variance: 0.1,
+    max_concurrent: 4,
+    length: Duration::from_millis(150),
+  },
+  SoundDef {
+    name: "ammo_pickup",
+    path: "/sounds/ammo_pickup.ogg",
+    category: SoundCategory::Pickups,
+    volume: 0.7,
+    pitch_variance: 0.05,
+    max_concurrent: 2,
+    length: Duration::from_millis(250),
+  },
+  SoundDef {
+    name: "enemy_hit",
+    path: "/sounds/enemy_hit.ogg",
+    category: SoundCategory::Impacts,
+    volume: 0.6,
+    pitch_variance: 0.15,
+    max_concurrent: 3,
+    length: Duration::from_millis(200),
+  },
+  SoundDef {
+    name: "death_explosion",
+    path: "/sounds/death_explosion.ogg",
+    category: SoundCategory::Explosions,
+    volume: 1.0,
+    pitch_variance: 0.1,
+    max_concurrent: 2,
+    length: Duration::from_millis(900),
+  },
+  SoundDef {
+    name: "out_of_bounds_explosion",
+    path: "/sounds/out_of_bounds_explosion.ogg",
+    category: SoundCategory::Explosions,
+    volume: 0.3,
+    pitch_variance: 0.2,
+    max_concurrent: 4,
+    length: Duration::from_millis(300),
+  },
+];
+
+pub fn sound_def(name: &str) -> Option<&'static SoundDef> {
+  SOUNDS.iter().find(|sound| sound.name == name)
+}
+
+// All between 0.0 (silent) and 1.0
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioSettings {
+  pub master: f32,
+  pub weapons: f32,
+  pub pickups: f32,
+  pub impacts: f32,
+  pub explosions: f32,
+}
+
+impl Default for AudioSettings {
+  fn default() -> AudioSettings {
+    AudioSettings {
+      master: 1.0,
+      weapons: 1.0,
+      pickups: 1.0,
+      impacts: 1.0,
+      explosions: 1.0,
+    }
+  }
+}
+
+impl AudioSettings {
+  // None is the master volume
+  pub fn volume(&self, category: Option<SoundCategory>) -> f32 {
+    match category {
+      None => self.master,
+      Some(SoundCategory::Weapons) => self.weapons,
+      Some(SoundCategory::Pickups) => self.pickups,
+      Some(SoundCategory::Impacts) => self.impacts,
+      Some(SoundCategory::Explosions) => self.explosions,
+    }
+  }
+
+  pub fn set_volume(&mut self, category: Option<SoundCategory>, volume: f32) {
+    match category {
+      None => self.master = volume,
+      Some(SoundCategory::Weapons) => self.weapons = volume,
+      Some(SoundCategory::Pickups) => self.pickups = volume,
+      Some(SoundCategory::Impacts) => self.impacts = volume,
+      Some(SoundCategory::Explosions) => self.explosions = volume,
+    }
+  }
+
+  pub fn all(&self) -> [f32; 5] {
+    [
+      self.master,
+      self.weapons,
+      self.pickups,
+      self.impacts,
+      self.explosions,
+    ]
+  }
+}
+
+// "master" or a category, as written in --volume and the console
+pub fn parse_volume_target(name: &str) -> Result<Option<SoundCategory>, String> {
+  match name {
+    "master" => Ok(None),
+    _ => name.parse::<SoundCategory>().map(Some),
+  }
+}
+
+// A sound the simulation decided to play, with its volume and pitch
+// already worked out
+#[derive(Debug, Clone, Serialize)]
+pub struct SoundPlay {
+  pub name: String,
+  // Simulated time since the start of the run
+  pub at: Duration,
+  pub volume: f32,
+  pub pitch: f32,
+}
+
+// Filled by AudioSystem during a tick and emptied into the audio backend
+// after it
+#[derive(Debug, Default)]
+pub struct SoundQueue(pub Vec<SoundPlay>);
@@ -0,0 +1,107 @@
+use ggez::graphics::Matrix4;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// How much trauma drains per second
+const TRAUMA_DECAY: f32 = 1.5;
+// Offset and rotation at full trauma
+const MAX_SHAKE_OFFSET: f32 = 24.0;
+const MAX_SHAKE_ANGLE: f32 = 0.05;
+
+// (x, y) is the world position shown at the centre of the screen.
+//
+// Screen shake is trauma based: events add trauma in 0.0..=1.0, it decays
+// over time, and the shake applied each frame is proportional to
+// trauma squared so small knocks stay subtle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Camera {
+  pub x: f32,
+  pub y: f32,
+  pub zoom: f32,
+  pub rotation: f32,
+  pub trauma: f32,
+  shake_x: f32,
+  shake_y: f32,
+  shake_angle: f32,
+}
+
+impl Camera {
+  pub fn new(x: f32, y: f32) -> Camera {
+    Camera {
+      x,
+      y,
+      zoom: 1.0,
+      rotation: 0.0,
+      trauma: 0.0,
+      shake_x: 0.0,
+      shake_y: 0.0,
+      shake_angle: 0.0,
+    }
+  }
+
+  pub fn add_trauma(&mut self, amount: f32) {
+    self.trauma = (self.trauma + amount).min(1.0);
+  }
+
+  // Shake is purely cosmetic, so it draws from thread_rng rather than
+  // GameRng to keep runs reproducible from their seed.
+  pub fn update_shake(&mut self, dt: f32) {
+    self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+
+    let shake = self.trauma * self.trauma;
+    let mut rng = rand::thread_rng();
+
+    self.shake_x = MAX_SHAKE_OFFSET * shake * rng.gen_range(-1.0, 1.0);
+    self.shake_y = MAX_SHAKE_OFFSET * shake * rng.gen_range(-1.0, 1.0);
+    self.shake_angle = MAX_SHAKE_ANGLE * shake * rng.gen_range(-1.0, 1.0);
+  }
+
+  // Maps world coordinates onto a viewport of the given size: move the
+  // camera position to the origin, zoom, rotate, then move the origin to
+  // the centre of the viewport.
+  pub fn transform(&self, viewport_width: f32, viewport_height: f32) -> Matrix4 {
+    let x = self.x + self.shake_x;
+    let y = self.y + self.shake_y;
+    let angle = self.rotation + self.shake_angle;
+    let cos = angle.cos() * self.zoom;
+    let sin = angle.sin() * self.zoom;
+
+    let tx = viewport_width / 2.0 - (cos * x - sin * y);
+    let ty = viewport_height / 2.0 - (sin * x + cos * y);
+
+    #[rustfmt::skip]
+    let transform = Matrix4::new(
+      cos, -sin, 0.0, tx,
+      sin,  cos, 0.0, ty,
+      0.0,  0.0, 1.0, 0.0,
+      0.0,  0.0, 0.0, 1.0,
+    );
+
+    transform
+  }
+
+  // The inverse of transform, for finding what's under the mouse
+  pub fn screen_to_world(
+    &self,
+    screen_x: f32,
+    screen_y: f32,
+    viewport_width: f32,
+    viewport_height: f32,
+  ) -> (f32, f32) {
+    let angle = self.rotation + self.shake_angle;
+    let dx = (screen_x - viewport_width / 2.0) / self.zoom;
+    let dy = (screen_y - viewport_height / 2.0) / self.zoom;
+    let (cos, sin) = (angle.cos(), angle.sin());
+
+    (
+      self.x + self.shake_x + cos * dx + sin * dy,
+      self.y + self.shake_y - sin * dx + cos * dy,
+    )
+  }
+}
+
+impl Default for Camera {
+  fn default() -> Camera {
+    Camera::new(0.0, 0.0)
+  }
+}
@@ -0,0 +1,72 @@
+use crate::resources::*;
+
+use specs::Entity;
+use std::collections::HashMap;
+
+pub type CollisionConstructor = fn(Entity, Entity) -> CollisionType;
+
+// Maps pairs of collider types onto the collision they represent. Pairs
+// are matched in either order, and the constructor always receives the
+// entities in the order the pair was registered in.
+pub struct CollisionRegistry {
+  handlers: HashMap<(ColliderType, ColliderType), CollisionConstructor>,
+}
+
+impl CollisionRegistry {
+  pub fn empty() -> CollisionRegistry {
+    CollisionRegistry {
+      handlers: HashMap::new(),
+    }
+  }
+
+  pub fn register(&mut self, a: ColliderType, b: ColliderType, constructor: CollisionConstructor) {
+    self.handlers.insert((a, b), constructor);
+  }
+
+  // Returns the collision, and whether the arguments had to be swapped to
+  // match the registered order.
+  pub fn resolve(
+    &self,
+    (type_a, entity_a): (ColliderType, Entity),
+    (type_b, entity_b): (ColliderType, Entity),
+  ) -> Option<(CollisionType, bool)> {
+    if let Some(constructor) = self.handlers.get(&(type_a, type_b)) {
+      Some((constructor(entity_a, entity_b), false))
+    } else if let Some(constructor) = self.handlers.get(&(type_b, type_a)) {
+      Some((constructor(entity_b, entity_a), true))
+    } else {
+      None
+    }
+  }
+}
+
+impl Default for CollisionRegistry {
+  fn default() -> CollisionRegistry {
+    let mut registry = CollisionRegistry::empty();
+
+    registry.register(ColliderType::Player, ColliderType::Ammo, |player, ammo| {
+      CollisionType::PlayerAmmo { player, ammo }
+    });
+
+    // Only reachable when friendly fire lets the collision groups meet
+    registry.register(
+      ColliderType::PlayerProjectile,
+      ColliderType::Player,
+      |projectile, player| CollisionType::ProjectilePlayer { projectile, player },
+    );
+
+    registry.register(
+      ColliderType::PlayerProjectile,
+      ColliderType::Enemy,
+      |projectile, enemy| CollisionType::ProjectileEnemy { projectile, enemy },
+    );
+
+    registry.register(
+      ColliderType::Player,
+      ColliderType::Enemy,
+      |player, enemy| CollisionType::PlayerEnemy { player, enemy },
+    );
+
+    registry
+  }
+}
@@ -1,12 +1,37 @@
-use nphysics2d::object::BodyHandle;
+use crate::components::*;
+use nalgebra::{Isometry2, Point2, Vector2};
+use ncollide2d::world::CollisionGroups;
+use nphysics2d::math::Velocity;
+use nphysics2d::object::{BodyHandle, BodyStatus, ColliderHandle};
 use nphysics2d::world::World;
+use rand::{FromEntropy, SeedableRng};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
 use shrev::EventChannel;
 use specs::Entity;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 use std::result::Result;
 use std::str::FromStr;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+mod audio;
+mod camera;
+mod collision_registry;
+mod particles;
+mod pool;
+mod profiler;
+mod spatial;
+mod stats;
+
+pub use audio::*;
+pub use camera::*;
+pub use collision_registry::*;
+pub use particles::*;
+pub use pool::*;
+pub use profiler::*;
+pub use spatial::*;
+pub use stats::*;
 
 #[derive(Debug, Default)]
 pub struct UpdateTime(pub Duration);
@@ -14,6 +39,134 @@ pub struct UpdateTime(pub Duration);
 pub struct PhysicsSim {
   pub world: World<f32>,
   pub bodies: HashMap<BodyHandle, Entity>,
+  pub colliders: HashMap<ColliderHandle, ColliderInfo>,
+  // The other way round, so nothing has to walk every collider to find a
+  // body's
+  body_colliders: HashMap<BodyHandle, Vec<ColliderHandle>>,
+  // Kept by PhysicsSystem, keyed by the colliders' handles so they're
+  // always walked in the same order. They live here rather than in the
+  // system so they go when the physics world is replaced, e.g. by a
+  // snapshot, and its handles start being reused.
+  pub contacts: BTreeMap<(usize, usize), ActiveContact>,
+}
+
+// A contact that has started and not yet stopped
+pub struct ActiveContact {
+  pub colliders: (ColliderHandle, ColliderHandle),
+  pub collision_type: CollisionType,
+  pub swapped: bool,
+  pub second_body: BodyHandle,
+  pub x: f32,
+  pub y: f32,
+  pub point: Point2<f32>,
+  pub normal: Vector2<f32>,
+}
+
+// What a collider is, so collisions can be dispatched on it
+#[derive(Debug, Clone, Copy)]
+pub struct ColliderInfo {
+  pub body: BodyHandle,
+  pub collider_type: ColliderType,
+}
+
+impl PhysicsSim {
+  // Links a body to its entity, and tags the colliders attached to it
+  pub fn register_body(
+    &mut self,
+    body: BodyHandle,
+    entity: Entity,
+    collider_type: Option<ColliderType>,
+  ) {
+    self.bodies.insert(body, entity);
+
+    let handles: Vec<ColliderHandle> = self
+      .world
+      .collider_world()
+      .body_colliders(body)
+      .map(|collider| collider.handle())
+      .collect();
+
+    if let Some(collider_type) = collider_type {
+      for handle in handles.iter() {
+        self.colliders.insert(
+          *handle,
+          ColliderInfo {
+            body,
+            collider_type,
+          },
+        );
+      }
+    }
+
+    self.body_colliders.insert(body, handles);
+  }
+
+  pub fn remove_body(&mut self, body: BodyHandle) {
+    self.world.remove_bodies(&[body]);
+    self.bodies.remove(&body);
+    for handle in self.body_colliders.remove(&body).unwrap_or_default() {
+      self.colliders.remove(&handle);
+    }
+  }
+
+  // Empty for a body that was never registered
+  pub fn body_colliders(&self, body: BodyHandle) -> &[ColliderHandle] {
+    self
+      .body_colliders
+      .get(&body)
+      .map_or(&[], |handles| handles.as_slice())
+  }
+
+  pub fn collider_type(&self, body: BodyHandle) -> Option<ColliderType> {
+    self
+      .body_colliders(body)
+      .iter()
+      .find_map(|handle| self.colliders.get(handle))
+      .map(|info| info.collider_type)
+  }
+
+  // Parks a pooled body: out of the simulation and colliding with nothing
+  pub fn disable_body(&mut self, body: BodyHandle) {
+    if let Some(rigid_body) = self.world.rigid_body_mut(body) {
+      rigid_body.set_status(BodyStatus::Disabled);
+      rigid_body.set_velocity(Velocity::zero());
+    }
+
+    let nothing = CollisionGroups::new()
+      .with_membership(&[])
+      .with_whitelist(&[]);
+    self.set_collision_groups(body, nothing);
+  }
+
+  // Brings a parked body back, with the collision groups it was built with
+  pub fn enable_body(
+    &mut self,
+    body: BodyHandle,
+    position: Isometry2<f32>,
+    velocity: Velocity<f32>,
+    collision_groups: Option<CollisionGroups>,
+  ) {
+    if let Some(rigid_body) = self.world.rigid_body_mut(body) {
+      rigid_body.set_status(BodyStatus::Dynamic);
+      rigid_body.set_position(position);
+      rigid_body.set_velocity(velocity);
+    }
+
+    if let Some(collision_groups) = collision_groups {
+      self.set_collision_groups(body, collision_groups);
+    }
+  }
+
+  fn set_collision_groups(&mut self, body: BodyHandle, collision_groups: CollisionGroups) {
+    let handles = self.body_colliders.get(&body).cloned().unwrap_or_default();
+
+    for handle in handles {
+      self
+        .world
+        .collider_world_mut()
+        .set_collision_groups(handle, collision_groups);
+    }
+  }
 }
 
 impl Default for PhysicsSim {
@@ -21,6 +174,9 @@ impl Default for PhysicsSim {
     PhysicsSim {
       world: World::default(),
       bodies: HashMap::default(),
+      colliders: HashMap::default(),
+      body_colliders: HashMap::default(),
+      contacts: BTreeMap::new(),
     }
   }
 }
@@ -29,43 +185,149 @@ pub type PhysicsWorld = World<f32>;
 
 pub type CollisionEvents = EventChannel<CollisionEvent>;
 
+pub type ProximityEvents = EventChannel<ProximityEvent>;
+
 // Used to map from BodyHandles in our Physics World,
 // to Entities in our Specs world
 pub type PhysicsEntities = HashMap<BodyHandle, Entity>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum CollisionType {
   PlayerAmmo { player: Entity, ammo: Entity },
+  ProjectilePlayer { projectile: Entity, player: Entity },
+  ProjectileEnemy { projectile: Entity, enemy: Entity },
+  PlayerEnemy { player: Entity, enemy: Entity },
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactPhase {
+  Started,
+  Ongoing,
+  Stopped,
+}
+
+// (x, y) is the position of the collision's second participant. point and
+// normal come from the deepest point of the contact manifold, with the
+// normal pointing from the first participant towards the second. Stopped
+// events carry the last point and normal seen while in contact.
 #[derive(Debug)]
 pub struct CollisionEvent {
   pub collision_type: CollisionType,
+  pub phase: ContactPhase,
   pub x: f32,
   pub y: f32,
+  pub point: Point2<f32>,
+  pub normal: Vector2<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProximityStatus {
+  Entered,
+  Exited,
+}
+
+// Sent when a sensor collider starts or stops overlapping another collider
+#[derive(Debug)]
+pub struct ProximityEvent {
+  pub collision_type: CollisionType,
+  pub status: ProximityStatus,
+  pub x: f32,
+  pub y: f32,
+}
+
+pub type DamageEvents = EventChannel<DamageEvent>;
+
+#[derive(Debug)]
+pub struct DamageEvent {
+  pub entity: Entity,
+  pub amount: f32,
+}
+
+pub type EffectEvents = EventChannel<EffectEvent>;
+
+#[derive(Debug)]
+pub struct EffectEvent {
+  pub effect: Effect,
+  pub position: PositionComponent,
+}
+
+pub type GameEvents = EventChannel<GameEvent>;
+
+// Things that happened this tick that presentation (camera, sound, ...)
+// might want to react to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum GameEvent {
+  PlayerHit {
+    x: f32,
+    y: f32,
+  },
+  DeathExplosion {
+    x: f32,
+    y: f32,
+  },
+  OutOfBoundsExplosion {
+    x: f32,
+    y: f32,
+  },
+  PlaySound {
+    name: String,
+    x: f32,
+    y: f32,
+  },
+  // player is None for anything not shot or collected by a player
+  ShotFired {
+    player: Option<usize>,
+    x: f32,
+    y: f32,
+  },
+  PickupCollected {
+    kind: ColliderType,
+    player: Option<usize>,
+    x: f32,
+    y: f32,
+  },
+  // A player's projectile hit an enemy, whether or not it died
+  EnemyHit {
+    x: f32,
+    y: f32,
+  },
+  Killed {
+    kind: ColliderType,
+    x: f32,
+    y: f32,
+  },
+  // Sent by scripts with emit(), for whatever is listening for the name
+  Scripted {
+    name: String,
+    x: f32,
+    y: f32,
+  },
 }
 
 pub const PLAYER_BODY_COLLISION_GROUP: usize = 0;
 pub const PLAYER_PROJECTILE_COLLISION_GROUP: usize = 1;
 pub const AMMO_BODY_COLLISION_GROUP: usize = 2;
+// Past every player's own groups
+pub const ENEMY_BODY_COLLISION_GROUP: usize = 3 + 2 * MAX_PLAYERS;
 
-#[derive(Debug)]
+pub const MAX_PLAYERS: usize = 2;
+
+// Besides the shared groups above, each player's ship and projectiles get
+// a group of their own, so friendly fire can tell them apart
+pub fn player_body_group(player: usize) -> usize {
+  3 + 2 * player
+}
+
+pub fn player_projectile_group(player: usize) -> usize {
+  4 + 2 * player
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ColliderType {
   Player,
   PlayerProjectile,
   Ammo,
-}
-
-impl FromStr for ColliderType {
-  type Err = String;
-
-  fn from_str(s: &str) -> Result<Self, Self::Err> {
-    match s {
-      "Player" => Ok(ColliderType::Player),
-      "PlayerProjectile" => Ok(ColliderType::PlayerProjectile),
-      "Ammo" => Ok(ColliderType::Ammo),
-      _ => Err(format!("Unable to parse {} as ColliderType", s)),
-    }
-  }
+  Enemy,
 }
 
 impl Display for ColliderType {
@@ -74,12 +336,48 @@ impl Display for ColliderType {
   }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Input {
   pub up: bool,
   pub down: bool,
   pub left: bool,
   pub right: bool,
   pub attack: bool,
+  // Analog values from a gamepad. The turn axis is in -1.0..=1.0,
+  // the boost and brake triggers are in 0.0..=1.0.
+  pub turn_axis: f32,
+  pub boost_axis: f32,
+  pub brake_axis: f32,
+}
+
+impl Input {
+  // Digital input wins over analog input, so a held key behaves
+  // exactly like a fully deflected stick or trigger.
+  pub fn turn(&self) -> f32 {
+    if self.left {
+      -1.0
+    } else if self.right {
+      1.0
+    } else {
+      self.turn_axis
+    }
+  }
+
+  pub fn boost(&self) -> f32 {
+    if self.up {
+      1.0
+    } else {
+      self.boost_axis
+    }
+  }
+
+  pub fn brake(&self) -> f32 {
+    if self.down {
+      1.0
+    } else {
+      self.brake_axis
+    }
+  }
 }
 
 impl Default for Input {
@@ -90,14 +388,50 @@ impl Default for Input {
       left: false,
       right: false,
       attack: false,
+      turn_axis: 0.0,
+      boost_axis: 0.0,
+      brake_axis: 0.0,
     }
   }
 }
 
+// One Input per local player, indexed by ControllableComponent::player
+#[derive(Debug, Clone)]
+pub struct Inputs(pub Vec<Input>);
+
+impl Inputs {
+  pub fn new(players: usize) -> Inputs {
+    Inputs(vec![Input::default(); players])
+  }
+
+  // Lets go of everything, for when something else takes the keyboard
+  pub fn release_all(&mut self) {
+    for input in self.0.iter_mut() {
+      *input = Input::default();
+    }
+  }
+}
+
+impl Default for Inputs {
+  fn default() -> Inputs {
+    Inputs::new(1)
+  }
+}
+
+// width and height are the size of the screen, world_width and
+// world_height the size of the playfield. The camera scrolls when the
+// world is larger than the screen. With friendly_fire off, players'
+// projectiles pass through every player ship rather than just their own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameSettings {
   pub width: u32,
   pub height: u32,
+  pub world_width: u32,
+  pub world_height: u32,
   pub scale: u32,
+  pub seed: u64,
+  pub players: usize,
+  pub friendly_fire: bool,
 }
 
 impl Default for GameSettings {
@@ -105,13 +439,63 @@ impl Default for GameSettings {
     GameSettings {
       width: 1920,
       height: 1080,
+      world_width: 1920,
+      world_height: 1080,
       scale: 1,
+      seed: 0,
+      players: 1,
+      friendly_fire: false,
     }
   }
 }
 
+// All gameplay randomness goes through this, so a run can be reproduced
+// from its seed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameRng(pub Pcg32);
+
+impl GameRng {
+  pub fn from_seed(seed: u64) -> GameRng {
+    GameRng(Pcg32::seed_from_u64(seed))
+  }
+}
+
+impl Default for GameRng {
+  fn default() -> GameRng {
+    GameRng(Pcg32::from_entropy())
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Score(pub u32);
+
+// The director script's set_var() values, kept between ticks
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScriptVars(pub BTreeMap<String, f64>);
+
+// Multiplies the frame time fed to the simulation; 0.5 runs at half speed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeScale(pub f32);
+
+impl Default for TimeScale {
+  fn default() -> TimeScale {
+    TimeScale(1.0)
+  }
+}
+
+// Collider outlines, velocities and live counters, toggled with F1. The
+// profiler's timings are toggled separately with F2. While frame stepping
+// (F4) the entity under the cursor, in world coordinates, is inspected.
+#[derive(Debug, Default)]
+pub struct DebugOverlay {
+  pub enabled: bool,
+  pub profiler: bool,
+  pub stepping: bool,
+  pub cursor: Option<(f32, f32)>,
+}
+
 pub struct SpawnInfo {
-  pub ammo_last: Instant,
+  pub ammo_since_last: Duration,
   pub ammo_every: Duration,
   pub ammo_count: u32,
   pub ammo_max: u32,
@@ -120,7 +504,7 @@ pub struct SpawnInfo {
 impl Default for SpawnInfo {
   fn default() -> SpawnInfo {
     SpawnInfo {
-      ammo_last: Instant::now(),
+      ammo_since_last: Duration::from_millis(0),
       ammo_every: Duration::from_millis(100),
       ammo_count: 0,
       ammo_max: 4,
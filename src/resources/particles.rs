@@ -0,0 +1,36 @@
+use crate::components::*;
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+// Particles are plain data rather than entities: they never collide, so
+// they don't need a rigid body, and there can be thousands of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Particle {
+  pub x: f32,
+  pub y: f32,
+  pub angle: f32,
+  pub vx: f32,
+  pub vy: f32,
+  pub age: Duration,
+  pub lifetime: Duration,
+  pub size: ParticleCurve,
+  pub color: ColorCurve,
+}
+
+impl Particle {
+  // How far through its life the particle is, in 0.0..=1.0
+  pub fn progress(&self) -> f32 {
+    let lifetime = self.lifetime.as_millis() as f32;
+    if lifetime <= 0.0 {
+      1.0
+    } else {
+      (self.age.as_millis() as f32 / lifetime).min(1.0)
+    }
+  }
+}
+
+pub const MAX_PARTICLES: usize = 10_000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Particles(pub Vec<Particle>);
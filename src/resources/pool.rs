@@ -0,0 +1,149 @@
+use nphysics2d::object::BodyHandle;
+use serde::{Deserialize, Serialize};
+use specs::Entity;
+use std::collections::HashMap;
+
+// High churn entities that get recycled instead of deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PoolKind {
+  Projectile,
+  ExplosionLine,
+  TrailParticle,
+}
+
+// How many entities of each kind may be alive at once. Spawns past the cap
+// are dropped.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolCaps {
+  pub projectile: usize,
+  pub explosion_line: usize,
+  pub trail_particle: usize,
+}
+
+impl Default for PoolCaps {
+  fn default() -> PoolCaps {
+    PoolCaps {
+      projectile: 256,
+      explosion_line: 1024,
+      trail_particle: 2048,
+    }
+  }
+}
+
+impl PoolCaps {
+  pub fn cap(&self, kind: PoolKind) -> usize {
+    match kind {
+      PoolKind::Projectile => self.projectile,
+      PoolKind::ExplosionLine => self.explosion_line,
+      PoolKind::TrailParticle => self.trail_particle,
+    }
+  }
+
+  pub fn set_cap(&mut self, kind: PoolKind, cap: usize) {
+    match kind {
+      PoolKind::Projectile => self.projectile = cap,
+      PoolKind::ExplosionLine => self.explosion_line = cap,
+      PoolKind::TrailParticle => self.trail_particle = cap,
+    }
+  }
+}
+
+// A parked entity, along with its body if it has one
+#[derive(Debug, Clone, Copy)]
+pub struct PooledEntity {
+  pub entity: Entity,
+  pub body: Option<BodyHandle>,
+}
+
+pub enum PoolSlot {
+  Reuse(PooledEntity),
+  Create,
+}
+
+#[derive(Debug, Default)]
+struct Pool {
+  cap: usize,
+  active: usize,
+  free: Vec<PooledEntity>,
+  released: Vec<PooledEntity>,
+}
+
+pub struct Pools {
+  pools: HashMap<PoolKind, Pool>,
+}
+
+impl Pools {
+  pub fn new(caps: &PoolCaps) -> Pools {
+    let pools = [
+      PoolKind::Projectile,
+      PoolKind::ExplosionLine,
+      PoolKind::TrailParticle,
+    ]
+    .iter()
+    .map(|kind| {
+      let pool = Pool {
+        cap: caps.cap(*kind),
+        ..Default::default()
+      };
+      (*kind, pool)
+    })
+    .collect();
+
+    Pools { pools }
+  }
+
+  // None when the pool is already at its cap
+  pub fn acquire(&mut self, kind: PoolKind) -> Option<PoolSlot> {
+    let pool = self.pools.get_mut(&kind).unwrap();
+
+    if pool.active >= pool.cap {
+      return None;
+    }
+    pool.active += 1;
+
+    match pool.free.pop() {
+      Some(pooled) => Some(PoolSlot::Reuse(pooled)),
+      None => Some(PoolSlot::Create),
+    }
+  }
+
+  pub fn release(&mut self, kind: PoolKind, pooled: PooledEntity) {
+    let pool = self.pools.get_mut(&kind).unwrap();
+    pool.active -= 1;
+    pool.released.push(pooled);
+  }
+
+  // Released entities only become free once the world has been maintained,
+  // so their deactivation can't land on top of a re-arm from the same tick.
+  pub fn recycle(&mut self) {
+    for pool in self.pools.values_mut() {
+      let released: Vec<PooledEntity> = pool.released.drain(..).collect();
+      pool.free.extend(released);
+    }
+  }
+
+  // Forgets every entity while keeping the caps, for when the world they
+  // lived in is thrown away
+  pub fn clear(&mut self) {
+    for pool in self.pools.values_mut() {
+      pool.active = 0;
+      pool.free.clear();
+      pool.released.clear();
+    }
+  }
+
+  pub fn cap(&self, kind: PoolKind) -> usize {
+    self.pools[&kind].cap
+  }
+
+  pub fn active(&self, kind: PoolKind) -> usize {
+    self.pools[&kind].active
+  }
+}
+
+impl Default for Pools {
+  fn default() -> Pools {
+    Pools::new(&PoolCaps::default())
+  }
+}
@@ -0,0 +1,162 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+// How many ticks the rolling stats cover
+const WINDOW: usize = 120;
+// Stop growing the trace past this, about an hour of ticks at 60fps
+const MAX_TRACE_EVENTS: usize = 4_000_000;
+
+#[derive(Debug, Clone)]
+pub struct SystemTimings {
+  pub name: &'static str,
+  pub last: Duration,
+  pub average: Duration,
+  pub p50: Duration,
+  pub p95: Duration,
+  pub p99: Duration,
+  pub max: Duration,
+}
+
+struct TraceEvent {
+  name: &'static str,
+  start: Duration,
+  duration: Duration,
+  thread: usize,
+}
+
+struct ProfilerData {
+  epoch: Instant,
+  // Insertion order, so the overlay lists systems in a stable order
+  names: Vec<&'static str>,
+  samples: HashMap<&'static str, VecDeque<Duration>>,
+  threads: Vec<ThreadId>,
+  trace: Option<Vec<TraceEvent>>,
+}
+
+// Per-system timings. Systems only need Read access to record into it, so
+// profiling doesn't serialize systems that would otherwise run in parallel.
+pub struct Profiler {
+  data: Mutex<ProfilerData>,
+}
+
+impl Profiler {
+  pub fn new(trace: bool) -> Profiler {
+    Profiler {
+      data: Mutex::new(ProfilerData {
+        epoch: Instant::now(),
+        names: vec![],
+        samples: HashMap::new(),
+        threads: vec![],
+        trace: if trace { Some(vec![]) } else { None },
+      }),
+    }
+  }
+
+  pub fn record(&self, name: &'static str, start: Instant, duration: Duration) {
+    let mut data = self.data.lock().unwrap();
+
+    if !data.samples.contains_key(name) {
+      data.names.push(name);
+    }
+    let samples = data.samples.entry(name).or_insert_with(VecDeque::new);
+    samples.push_back(duration);
+    if samples.len() > WINDOW {
+      samples.pop_front();
+    }
+
+    let id = thread::current().id();
+    let thread = match data.threads.iter().position(|t| *t == id) {
+      Some(thread) => thread,
+      None => {
+        data.threads.push(id);
+        data.threads.len() - 1
+      }
+    };
+
+    let since_epoch = start.duration_since(data.epoch);
+    if let Some(trace) = data.trace.as_mut() {
+      if trace.len() < MAX_TRACE_EVENTS {
+        trace.push(TraceEvent {
+          name,
+          start: since_epoch,
+          duration,
+          thread,
+        });
+      }
+    }
+  }
+
+  pub fn timings(&self) -> Vec<SystemTimings> {
+    let data = self.data.lock().unwrap();
+
+    data
+      .names
+      .iter()
+      .map(|name| {
+        let samples = &data.samples[name];
+        let mut sorted: Vec<Duration> = samples.iter().cloned().collect();
+        sorted.sort();
+
+        let percentile = |p: f32| sorted[((sorted.len() - 1) as f32 * p).round() as usize];
+        let total: Duration = sorted.iter().sum();
+
+        SystemTimings {
+          name,
+          last: *samples.back().unwrap(),
+          average: total / sorted.len() as u32,
+          p50: percentile(0.5),
+          p95: percentile(0.95),
+          p99: percentile(0.99),
+          max: *sorted.last().unwrap(),
+        }
+      })
+      .collect()
+  }
+
+  // Writes everything recorded so far in the Chrome trace event format,
+  // which chrome://tracing and Perfetto can open.
+  pub fn write_trace(&self, path: &Path) -> Result<(), String> {
+    let data = self.data.lock().unwrap();
+    let trace = match data.trace.as_ref() {
+      Some(trace) => trace,
+      None => return Ok(()),
+    };
+
+    let error = |e: std::io::Error| format!("Unable to write trace {}: {}", path.display(), e);
+    let mut file = BufWriter::new(File::create(path).map_err(error)?);
+
+    let mut write = || -> std::io::Result<()> {
+      writeln!(file, "{{\"traceEvents\":[")?;
+      for (i, event) in trace.iter().enumerate() {
+        writeln!(
+          file,
+          "{{\"name\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}}}{}",
+          event.name,
+          micros(event.start),
+          micros(event.duration),
+          event.thread,
+          if i + 1 < trace.len() { "," } else { "" }
+        )?;
+      }
+      writeln!(file, "]}}")?;
+      file.flush()
+    };
+
+    write().map_err(error)
+  }
+}
+
+impl Default for Profiler {
+  fn default() -> Profiler {
+    Profiler::new(false)
+  }
+}
+
+fn micros(duration: Duration) -> f64 {
+  duration.as_secs() as f64 * 1_000_000.0 + duration.subsec_nanos() as f64 / 1_000.0
+}
@@ -0,0 +1,321 @@
+use crate::resources::*;
+
+use nalgebra::{Isometry2, Point2, Vector2};
+use ncollide2d::bounding_volume::{BoundingVolume, AABB};
+use ncollide2d::query::{self, PointQuery, Ray, RayCast};
+use ncollide2d::shape::{Cuboid, ShapeHandle};
+use specs::Entity;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+const DEFAULT_CELL_SIZE: f32 = 64.0;
+
+struct SpatialEntry {
+  entity: Entity,
+  collider_type: ColliderType,
+  aabb: AABB<f32>,
+  shape: ShapeHandle<f32>,
+  position: Isometry2<f32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+  pub entity: Entity,
+  pub collider_type: ColliderType,
+  pub distance: f32,
+  pub point: Point2<f32>,
+}
+
+// A spatial hash over every tagged collider, rebuilt each tick by
+// SpatialIndexSystem. Queries take the collider types they care about,
+// test the colliders' real shapes and return each matching entity once,
+// even if it has several colliders. Ties are broken by entity id, so
+// results don't depend on hashing order.
+pub struct SpatialQuery {
+  cell_size: f32,
+  cells: HashMap<(i32, i32), Vec<usize>>,
+  // The lowest and highest cells with anything in them
+  occupied: Option<((i32, i32), (i32, i32))>,
+  entries: Vec<SpatialEntry>,
+}
+
+impl SpatialQuery {
+  pub fn new(cell_size: f32) -> SpatialQuery {
+    SpatialQuery {
+      cell_size,
+      cells: HashMap::new(),
+      occupied: None,
+      entries: vec![],
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.cells.clear();
+    self.occupied = None;
+    self.entries.clear();
+  }
+
+  pub fn insert(
+    &mut self,
+    entity: Entity,
+    collider_type: ColliderType,
+    shape: ShapeHandle<f32>,
+    position: Isometry2<f32>,
+  ) {
+    let aabb = shape.aabb(&position);
+    let (min_x, min_y) = self.cell(aabb.mins());
+    let (max_x, max_y) = self.cell(aabb.maxs());
+
+    self.occupied = Some(match self.occupied {
+      Some(((lx, ly), (hx, hy))) => (
+        (lx.min(min_x), ly.min(min_y)),
+        (hx.max(max_x), hy.max(max_y)),
+      ),
+      None => ((min_x, min_y), (max_x, max_y)),
+    });
+
+    let index = self.entries.len();
+    for cx in min_x..=max_x {
+      for cy in min_y..=max_y {
+        self
+          .cells
+          .entry((cx, cy))
+          .or_insert_with(Vec::new)
+          .push(index);
+      }
+    }
+
+    self.entries.push(SpatialEntry {
+      entity,
+      collider_type,
+      aabb,
+      shape,
+      position,
+    });
+  }
+
+  // Entities with a collider touching the circle
+  pub fn in_radius(
+    &self,
+    center: Point2<f32>,
+    radius: f32,
+    filter: &[ColliderType],
+  ) -> Vec<Entity> {
+    let extent = Vector2::new(radius, radius);
+    let candidates = self.candidates(&AABB::new(center - extent, center + extent), filter);
+
+    unique_entities(
+      candidates
+        .into_iter()
+        .filter(|entry| distance_to_shape(entry, &center) <= radius),
+    )
+  }
+
+  // Entities with a collider overlapping the box
+  pub fn in_aabb(
+    &self,
+    mins: Point2<f32>,
+    maxs: Point2<f32>,
+    filter: &[ColliderType],
+  ) -> Vec<Entity> {
+    let aabb = AABB::new(mins, maxs);
+    let candidates = self.candidates(&aabb, filter);
+
+    let half = (maxs - mins) / 2.0;
+    let cuboid = Cuboid::new(half);
+    let center = Isometry2::new(mins.coords + half, 0.0);
+
+    unique_entities(candidates.into_iter().filter(|entry| {
+      entry.aabb.intersects(&aabb)
+        && query::distance(&center, &cuboid, &entry.position, &*entry.shape) <= 0.0
+    }))
+  }
+
+  // Up to k entities, closest first. Walks rings of cells outwards from
+  // the point, and stops once nothing further out could be any closer.
+  pub fn nearest(&self, point: Point2<f32>, k: usize, filter: &[ColliderType]) -> Vec<Entity> {
+    let ((low_x, low_y), (high_x, high_y)) = match self.occupied {
+      Some(occupied) if k > 0 => occupied,
+      _ => return vec![],
+    };
+    let (cx, cy) = self.cell(&point);
+    let last_ring = (cx - low_x)
+      .max(high_x - cx)
+      .max(cy - low_y)
+      .max(high_y - cy);
+
+    let mut seen = HashSet::new();
+    let mut closest: HashMap<Entity, f32> = HashMap::new();
+
+    for ring in 0..=last_ring.max(0) {
+      for (x, y) in ring_cells(cx, cy, ring) {
+        let cell = match self.cells.get(&(x, y)) {
+          Some(cell) => cell,
+          None => continue,
+        };
+
+        for &index in cell {
+          let entry = &self.entries[index];
+          if !seen.insert(index) || !filter.contains(&entry.collider_type) {
+            continue;
+          }
+
+          let distance = distance_to_shape(entry, &point);
+          if distance.is_nan() {
+            continue;
+          }
+          let best = closest.entry(entry.entity).or_insert(distance);
+          *best = best.min(distance);
+        }
+      }
+
+      // The point is somewhere in the centre cell, so anything beyond this
+      // ring is at least this far away
+      let reach = ring as f32 * self.cell_size;
+      if closest
+        .values()
+        .filter(|distance| **distance <= reach)
+        .count()
+        >= k
+      {
+        break;
+      }
+    }
+
+    let mut closest: Vec<(Entity, f32)> = closest.into_iter().collect();
+    closest.sort_by(|a, b| by_distance((a.1, a.0), (b.1, b.0)));
+
+    closest
+      .into_iter()
+      .take(k)
+      .map(|(entity, _)| entity)
+      .collect()
+  }
+
+  // The first collider hit by a ray, tested against the exact shape
+  pub fn raycast(
+    &self,
+    origin: Point2<f32>,
+    direction: Vector2<f32>,
+    max_distance: f32,
+    filter: &[ColliderType],
+  ) -> Option<RayHit> {
+    let direction = direction.normalize();
+    let end = origin + direction * max_distance;
+    let bounds = AABB::new(
+      Point2::new(origin.x.min(end.x), origin.y.min(end.y)),
+      Point2::new(origin.x.max(end.x), origin.y.max(end.y)),
+    );
+    let ray = Ray::new(origin, direction);
+
+    self
+      .candidates(&bounds, filter)
+      .into_iter()
+      .filter_map(|entry| {
+        entry
+          .shape
+          .as_ray_cast()
+          .and_then(|shape| shape.toi_with_ray(&entry.position, &ray, true))
+          .filter(|distance| *distance <= max_distance)
+          .map(|distance| RayHit {
+            entity: entry.entity,
+            collider_type: entry.collider_type,
+            distance,
+            point: ray.point_at(distance),
+          })
+      })
+      .filter(|hit| !hit.distance.is_nan())
+      .min_by(|a, b| by_distance((a.distance, a.entity), (b.distance, b.entity)))
+  }
+
+  fn cell(&self, point: &Point2<f32>) -> (i32, i32) {
+    (
+      (point.x / self.cell_size).floor() as i32,
+      (point.y / self.cell_size).floor() as i32,
+    )
+  }
+
+  // Entries in the cells covered by the box, each once
+  fn candidates(&self, aabb: &AABB<f32>, filter: &[ColliderType]) -> Vec<&SpatialEntry> {
+    let (min_x, min_y) = self.cell(aabb.mins());
+    let (max_x, max_y) = self.cell(aabb.maxs());
+
+    let mut indices = vec![];
+    for cx in min_x..=max_x {
+      for cy in min_y..=max_y {
+        if let Some(cell) = self.cells.get(&(cx, cy)) {
+          indices.extend(cell.iter().cloned());
+        }
+      }
+    }
+    indices.sort();
+    indices.dedup();
+
+    indices
+      .into_iter()
+      .map(|index| &self.entries[index])
+      .filter(|entry| filter.contains(&entry.collider_type))
+      .collect()
+  }
+}
+
+impl Default for SpatialQuery {
+  fn default() -> SpatialQuery {
+    SpatialQuery::new(DEFAULT_CELL_SIZE)
+  }
+}
+
+// Distance from the point to the collider's shape, 0.0 inside it. Shapes
+// that can't answer fall back to their bounding box.
+fn distance_to_shape(entry: &SpatialEntry, point: &Point2<f32>) -> f32 {
+  match entry.shape.as_point_query() {
+    Some(shape) => shape.distance_to_point(&entry.position, point, true),
+    None => distance_to_aabb(&entry.aabb, point),
+  }
+}
+
+// Closest first, then lowest entity id
+fn by_distance(a: (f32, Entity), b: (f32, Entity)) -> Ordering {
+  a.0
+    .partial_cmp(&b.0)
+    .unwrap_or(Ordering::Equal)
+    .then(a.1.id().cmp(&b.1.id()))
+}
+
+// The cells exactly `ring` cells away from (cx, cy), as a square outline
+fn ring_cells(cx: i32, cy: i32, ring: i32) -> Vec<(i32, i32)> {
+  if ring == 0 {
+    return vec![(cx, cy)];
+  }
+
+  let mut cells = vec![];
+  for x in (cx - ring)..=(cx + ring) {
+    cells.push((x, cy - ring));
+    cells.push((x, cy + ring));
+  }
+  for y in (cy - ring + 1)..=(cy + ring - 1) {
+    cells.push((cx - ring, y));
+    cells.push((cx + ring, y));
+  }
+  cells
+}
+
+fn distance_to_aabb(aabb: &AABB<f32>, point: &Point2<f32>) -> f32 {
+  let dx = (aabb.mins().x - point.x)
+    .max(point.x - aabb.maxs().x)
+    .max(0.0);
+  let dy = (aabb.mins().y - point.y)
+    .max(point.y - aabb.maxs().y)
+    .max(0.0);
+  (dx * dx + dy * dy).sqrt()
+}
+
+fn unique_entities<'a, I: Iterator<Item = &'a SpatialEntry>>(entries: I) -> Vec<Entity> {
+  let mut result: Vec<Entity> = vec![];
+  for entry in entries {
+    if !result.contains(&entry.entity) {
+      result.push(entry.entity);
+    }
+  }
+  result
+}
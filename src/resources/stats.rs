@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsSample {
+  pub time: f32,
+  pub score: u32,
+  pub hp: f32,
+  pub entities: usize,
+  pub bodies: usize,
+  pub particles: usize,
+  pub shots_fired: u32,
+  pub kills: u32,
+}
+
+// What each local player did, indexed like Inputs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerStats {
+  pub shots_fired: u32,
+  pub pickups: u32,
+  pub damage_taken: f32,
+}
+
+// Everything worth knowing about a run, gathered by StatsSystem and
+// written out as JSON once the run is over.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunStats {
+  pub seed: u64,
+  pub ship: String,
+  // Simulated seconds, so replays report the same duration
+  pub duration: f32,
+  pub score: u32,
+  pub kills: BTreeMap<String, u32>,
+  pub shots_fired: u32,
+  pub hits: u32,
+  pub pickups: BTreeMap<String, u32>,
+  pub damage_taken: f32,
+  pub peak_entities: usize,
+  pub peak_bodies: usize,
+  pub peak_particles: usize,
+  pub players: Vec<PlayerStats>,
+  // One sample per simulated second
+  pub samples: Vec<StatsSample>,
+}
+
+impl RunStats {
+  pub fn new(seed: u64, ship: &str, players: usize) -> RunStats {
+    RunStats {
+      seed,
+      ship: ship.to_owned(),
+      players: vec![PlayerStats::default(); players],
+      ..Default::default()
+    }
+  }
+
+  // Writes to dir/run-<unix time>-<seed>.json, creating dir if needed
+  pub fn write(&self, dir: &Path) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir)
+      .map_err(|e| format!("Unable to create stats directory {}: {}", dir.display(), e))?;
+
+    let timestamp = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or(0);
+    let path = dir.join(format!("run-{}-{}.json", timestamp, self.seed));
+
+    let file = File::create(&path)
+      .map_err(|e| format!("Unable to write stats {}: {}", path.display(), e))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), self)
+      .map_err(|e| format!("Unable to write stats {}: {}", path.display(), e))?;
+
+    Ok(path)
+  }
+}
@@ -0,0 +1,404 @@
+use crate::entities::*;
+use crate::resources::*;
+
+use rand::Rng;
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST, FLOAT, INT};
+use specs::Entity;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// Runs every tick with the tick length, for spawn patterns and the like
+pub const DIRECTOR_SCRIPT: &str = "director";
+
+// A script that runs away is stopped after this many operations per call
+const MAX_OPERATIONS: u64 = 100_000;
+
+// Used when an enemy script has no enemy() function, or leaves a field out
+const DEFAULT_ENEMY_RADIUS: f32 = 12.0;
+const DEFAULT_ENEMY_HP: f32 = 30.0;
+const DEFAULT_ENEMY_SCORE: u32 = 5;
+
+// What a script call can see and do. ScriptSystem fills it in before each
+// call and carries out the queued commands once the scripts have run, so
+// scripts never touch the ECS directly.
+#[derive(Default)]
+pub struct ScriptContext {
+  pub target: Option<ScriptTarget>,
+  // Indexed by player, None once their ship is gone
+  pub players: Vec<Option<(f32, f32)>>,
+  pub world_size: (f32, f32),
+  // Borrowed from the world while scripts run, so random() stays
+  // reproducible from the seed
+  pub rng: Option<GameRng>,
+  // The calling entity's vars, or the director's
+  pub vars: BTreeMap<String, f64>,
+  pub commands: Vec<ScriptCommand>,
+  // Errors from the API functions already printed, so a script that keeps
+  // misusing one complains once rather than every tick
+  reported: HashSet<String>,
+}
+
+impl ScriptContext {
+  fn report(&mut self, error: String) {
+    if !self.reported.contains(&error) {
+      eprintln!("{}", error);
+      self.reported.insert(error);
+    }
+  }
+}
+
+// Where a call is happening: the entity being updated, or just the
+// position of an effect once its entity is gone
+#[derive(Debug, Clone)]
+pub struct ScriptTarget {
+  pub entity: Option<Entity>,
+  pub x: f32,
+  pub y: f32,
+  pub angle: f32,
+  pub velocity: (f32, f32),
+  // From the spatial index, None once every ship is gone
+  pub nearest_player: Option<(f32, f32)>,
+}
+
+#[derive(Debug)]
+pub enum ScriptCommand {
+  Spawn { prefab: Prefab, x: f32, y: f32 },
+  SpawnEnemy { script: String, x: f32, y: f32 },
+  SetVelocity { entity: Entity, x: f32, y: f32 },
+  Emit { name: String, x: f32, y: f32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EnemyStats {
+  pub radius: f32,
+  pub hp: f32,
+  pub score: u32,
+}
+
+// Every .rhai file in a directory, compiled once at startup and named
+// after its file. Scripts only get the functions registered here; they
+// can't reach the filesystem or the rest of the game.
+pub struct ScriptHost {
+  engine: Engine,
+  scripts: HashMap<String, AST>,
+  context: Arc<Mutex<ScriptContext>>,
+  // Functions that have errored, so a broken script complains once
+  // rather than every tick
+  failed: HashSet<(String, String)>,
+}
+
+impl ScriptHost {
+  pub fn empty() -> ScriptHost {
+    let context = Arc::new(Mutex::new(ScriptContext::default()));
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    register_api(&mut engine, &context);
+
+    ScriptHost {
+      engine,
+      scripts: HashMap::new(),
+      context,
+      failed: HashSet::new(),
+    }
+  }
+
+  pub fn load(dir: &Path) -> Result<ScriptHost, String> {
+    let mut host = ScriptHost::empty();
+
+    let entries = fs::read_dir(dir)
+      .map_err(|e| format!("Unable to read scripts directory {}: {}", dir.display(), e))?;
+
+    for entry in entries {
+      let path = entry
+        .map_err(|e| format!("Unable to read scripts directory {}: {}", dir.display(), e))?
+        .path();
+      if path
+        .extension()
+        .map_or(true, |extension| extension != "rhai")
+      {
+        continue;
+      }
+
+      let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(name) => name.to_owned(),
+        None => continue,
+      };
+      let source = fs::read_to_string(&path)
+        .map_err(|e| format!("Unable to read script {}: {}", path.display(), e))?;
+      let ast = host
+        .engine
+        .compile(&source)
+        .map_err(|e| format!("Invalid script {}: {}", path.display(), e))?;
+
+      host.scripts.insert(name, ast);
+    }
+
+    Ok(host)
+  }
+
+  pub fn has(&self, script: &str) -> bool {
+    self.scripts.contains_key(script)
+  }
+
+  pub fn context(&self) -> std::sync::MutexGuard<ScriptContext> {
+    self.context.lock().unwrap()
+  }
+
+  // Calls a function with the argument, if there is one. Returns None if
+  // the script or the function doesn't exist, or the call failed.
+  pub fn call(&mut self, script: &str, function: &str, arg: Option<Dynamic>) -> Option<Dynamic> {
+    let key = (script.to_owned(), function.to_owned());
+    if self.failed.contains(&key) {
+      return None;
+    }
+
+    let ast = self.scripts.get(script)?;
+    let arity = if arg.is_some() { 1 } else { 0 };
+    let mut scope = Scope::new();
+    let result = match arg {
+      Some(arg) => self
+        .engine
+        .call_fn::<_, Dynamic>(&mut scope, ast, function, (arg,)),
+      None => self
+        .engine
+        .call_fn::<_, Dynamic>(&mut scope, ast, function, ()),
+    };
+
+    match result {
+      Ok(value) => Some(value),
+      Err(e) => {
+        // Scripts only define the functions they need
+        let missing = match *e {
+          EvalAltResult::ErrorFunctionNotFound(ref signature, _) => {
+            is_signature(signature, function, arity)
+          }
+          _ => false,
+        };
+        if !missing {
+          eprintln!("Script {} failed in {}(): {}", script, function, e);
+        }
+        self.failed.insert(key);
+        None
+      }
+    }
+  }
+
+  // From the script's enemy() function, which returns a map like
+  // #{ radius: 12.0, hp: 30.0, score: 5 }
+  pub fn enemy_stats(&mut self, script: &str) -> EnemyStats {
+    let map = self
+      .call(script, "enemy", None)
+      .and_then(|value| value.try_cast::<Map>())
+      .unwrap_or_default();
+    let field = |name: &str| map.get(name).and_then(number);
+
+    EnemyStats {
+      radius: field("radius").unwrap_or(DEFAULT_ENEMY_RADIUS),
+      hp: field("hp").unwrap_or(DEFAULT_ENEMY_HP),
+      score: field("score").map_or(DEFAULT_ENEMY_SCORE, |score| score.max(0.0) as u32),
+    }
+  }
+}
+
+// Whether a function-not-found error is about the function we called,
+// with the arguments we called it with, rather than something it calls
+// going missing. Rhai writes the signature as "name" or "name (type, ...)".
+fn is_signature(signature: &str, function: &str, arity: usize) -> bool {
+  if signature == function {
+    return true;
+  }
+
+  let args = match signature
+    .strip_prefix(function)
+    .and_then(|rest| rest.strip_prefix(" ("))
+    .and_then(|rest| rest.strip_suffix(")"))
+  {
+    Some(args) => args,
+    None => return false,
+  };
+  let count = if args.trim().is_empty() {
+    0
+  } else {
+    args.split(',').count()
+  };
+
+  count == arity
+}
+
+// The functions scripts can call. Reads come from the context as it was
+// set up for the current call; writes are queued as commands.
+fn register_api(engine: &mut Engine, context: &Arc<Mutex<ScriptContext>>) {
+  let ctx = context.clone();
+  engine.register_fn("spawn", move |prefab: &str, x: FLOAT, y: FLOAT| -> bool {
+    match prefab.parse::<Prefab>() {
+      Ok(prefab) => {
+        ctx.lock().unwrap().commands.push(ScriptCommand::Spawn {
+          prefab,
+          x: x as f32,
+          y: y as f32,
+        });
+        true
+      }
+      Err(e) => {
+        ctx.lock().unwrap().report(format!("spawn(): {}", e));
+        false
+      }
+    }
+  });
+
+  let ctx = context.clone();
+  engine.register_fn("spawn_enemy", move |script: &str, x: FLOAT, y: FLOAT| {
+    ctx
+      .lock()
+      .unwrap()
+      .commands
+      .push(ScriptCommand::SpawnEnemy {
+        script: script.to_owned(),
+        x: x as f32,
+        y: y as f32,
+      });
+  });
+
+  // #{x, y, angle} of the entity being updated or the effect being run,
+  // () in the director
+  let ctx = context.clone();
+  engine.register_fn("position", move || -> Dynamic {
+    match &ctx.lock().unwrap().target {
+      Some(target) => {
+        let mut map = point(target.x, target.y);
+        map.insert("angle".into(), (target.angle as FLOAT).into());
+        Dynamic::from(map)
+      }
+      None => Dynamic::UNIT,
+    }
+  });
+
+  let ctx = context.clone();
+  engine.register_fn("velocity", move || -> Dynamic {
+    match &ctx.lock().unwrap().target {
+      Some(target) => Dynamic::from(point(target.velocity.0, target.velocity.1)),
+      None => Dynamic::UNIT,
+    }
+  });
+
+  let ctx = context.clone();
+  engine.register_fn("set_velocity", move |x: FLOAT, y: FLOAT| -> bool {
+    let mut ctx = ctx.lock().unwrap();
+    match ctx.target.as_ref().and_then(|target| target.entity) {
+      Some(entity) => {
+        ctx.commands.push(ScriptCommand::SetVelocity {
+          entity,
+          x: x as f32,
+          y: y as f32,
+        });
+        true
+      }
+      None => {
+        ctx.report("set_velocity(): there's no entity to move here".to_owned());
+        false
+      }
+    }
+  });
+
+  // The nearest ship to the current position, or player one's in the
+  // director. () when there's no ship left.
+  let ctx = context.clone();
+  engine.register_fn("player_position", move || -> Dynamic {
+    let ctx = ctx.lock().unwrap();
+    let nearest = match &ctx.target {
+      Some(target) => target.nearest_player,
+      None => ctx.players.iter().filter_map(|ship| *ship).next(),
+    };
+
+    nearest.map_or(Dynamic::UNIT, |(x, y)| Dynamic::from(point(x, y)))
+  });
+
+  // Counting from 1, like the HUD
+  let ctx = context.clone();
+  engine.register_fn("player_position", move |player: INT| -> Dynamic {
+    let ctx = ctx.lock().unwrap();
+    let ship = if player >= 1 {
+      ctx.players.get(player as usize - 1).cloned().flatten()
+    } else {
+      None
+    };
+
+    ship.map_or(Dynamic::UNIT, |(x, y)| Dynamic::from(point(x, y)))
+  });
+
+  let ctx = context.clone();
+  engine.register_fn("world_size", move || -> Map {
+    let (width, height) = ctx.lock().unwrap().world_size;
+    let mut map = Map::new();
+    map.insert("width".into(), (width as FLOAT).into());
+    map.insert("height".into(), (height as FLOAT).into());
+    map
+  });
+
+  let ctx = context.clone();
+  engine.register_fn("random", move |low: FLOAT, high: FLOAT| -> FLOAT {
+    if high <= low {
+      return low;
+    }
+    match &mut ctx.lock().unwrap().rng {
+      Some(rng) => rng.0.gen_range(low, high),
+      None => low,
+    }
+  });
+
+  // State that outlives the call, 0.0 until it's been set
+  let ctx = context.clone();
+  engine.register_fn("var", move |name: &str| -> FLOAT {
+    ctx.lock().unwrap().vars.get(name).cloned().unwrap_or(0.0)
+  });
+
+  let ctx = context.clone();
+  engine.register_fn("set_var", move |name: &str, value: FLOAT| {
+    ctx.lock().unwrap().vars.insert(name.to_owned(), value);
+  });
+
+  // Sends a GameEvent::Scripted from the current position
+  let ctx = context.clone();
+  engine.register_fn("emit", move |name: &str| {
+    let mut ctx = ctx.lock().unwrap();
+    let (x, y) = ctx
+      .target
+      .as_ref()
+      .map_or((0.0, 0.0), |target| (target.x, target.y));
+    ctx.commands.push(ScriptCommand::Emit {
+      name: name.to_owned(),
+      x,
+      y,
+    });
+  });
+
+  let ctx = context.clone();
+  engine.register_fn("emit", move |name: &str, x: FLOAT, y: FLOAT| {
+    ctx.lock().unwrap().commands.push(ScriptCommand::Emit {
+      name: name.to_owned(),
+      x: x as f32,
+      y: y as f32,
+    });
+  });
+}
+
+fn point(x: f32, y: f32) -> Map {
+  let mut map = Map::new();
+  map.insert("x".into(), (x as FLOAT).into());
+  map.insert("y".into(), (y as FLOAT).into());
+  map
+}
+
+// Scripts are free to write 30 or 30.0
+fn number(value: &Dynamic) -> Option<f32> {
+  let value = value.clone();
+  if value.is::<FLOAT>() {
+    Some(value.cast::<FLOAT>() as f32)
+  } else if value.is::<INT>() {
+    Some(value.cast::<INT>() as f32)
+  } else {
+    None
+  }
+}
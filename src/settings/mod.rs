@@ -0,0 +1,481 @@
+use crate::resources::*;
+
+use ggez::conf::{FullscreenType, WindowMode};
+use serde::Deserialize;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const DEFAULT_SETTINGS_PATH: &str = "settings.toml";
+pub const DEFAULT_STATS_DIR: &str = "runs";
+// Used when it exists, unless --no-scripts is passed
+pub const DEFAULT_SCRIPTS_DIR: &str = "scripts";
+
+pub const USAGE: &str = "Usage: bytepath-rs [OPTIONS]
+
+Options:
+  --config <PATH>         Settings file to read (default: settings.toml)
+  --resolution <WxH>      Window resolution, e.g. 1920x1080
+  --scale <N>             Pixel scale, must divide the resolution
+  --world <WxH>           Size of the playfield (default: the resolution)
+  --fullscreen            Run fullscreen
+  --windowed              Run in a window
+  --vsync                 Enable vsync
+  --no-vsync              Disable vsync
+  --seed <N>              Seed for the game's random number generator
+  --players <N>           Local players, 1 or 2. Player two steers with
+                          WASD and fires with F, or uses a gamepad
+  --friendly-fire         Let players' projectiles hit each other's ships
+  --no-friendly-fire      Players' projectiles pass through all ships
+  --headless              Run the simulation without a window. Needs
+                          --replay or --ticks to know when to stop
+  --ticks <N>             Stop a headless run after N ticks
+  --replay <PATH>         Play back recorded input from PATH
+  --record <PATH>         Record input to PATH
+  --load <PATH>           Start from a snapshot saved with the console
+  --stats-dir <PATH>      Where run statistics are written (default: runs)
+  --profile-trace <PATH>  Write per-system timings to PATH as a Chrome trace
+  --rewind <SECONDS>      Keep the last SECONDS of ticks to scrub through
+                          with F3 (default: 0, off)
+  --scripts <DIR>         Load enemy and spawn scripts (*.rhai) from DIR
+                          (default: scripts, if it exists)
+  --no-scripts            Play without enemies or any other scripts
+  --pool-cap <KIND=N>     Most live entities for a pool: projectile,
+                          explosion_line or trail_particle
+  --volume <NAME=V>       Volume from 0 to 1 for master, weapons, pickups,
+                          impacts or explosions
+  --mute                  Play no sound
+  --sound-log <PATH>      Write the sounds a headless run would have played
+                          to PATH as JSON
+  --net-bind <ADDR>       Play over the network from this UDP address,
+                          e.g. 127.0.0.1:7000. Needs --net-peer and --seed
+  --net-peer <ADDR>       The other instance's --net-bind address
+  --net-player <N>        Which player this instance controls, 1 or 2
+                          (default: 1), counting from 1 here and as
+                          net_player in the settings file. Each side must
+                          pick a different one
+  --input-delay <TICKS>   Ticks between pressing a key and it taking effect
+                          in a networked game (default: 2)";
+
+// Everything configurable at startup. Values come from the settings file
+// first, then get overridden by whatever was passed on the command line.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+  pub width: u32,
+  pub height: u32,
+  pub world_width: Option<u32>,
+  pub world_height: Option<u32>,
+  pub scale: u32,
+  pub fullscreen: bool,
+  pub vsync: bool,
+  pub seed: Option<u64>,
+  pub players: usize,
+  pub friendly_fire: bool,
+  pub headless: bool,
+  pub ticks: Option<u64>,
+  pub replay: Option<PathBuf>,
+  pub record: Option<PathBuf>,
+  pub load: Option<PathBuf>,
+  pub stats_dir: PathBuf,
+  pub profile_trace: Option<PathBuf>,
+  pub rewind_seconds: f32,
+  pub scripts_dir: Option<PathBuf>,
+  pub pools: PoolCaps,
+  pub audio: AudioSettings,
+  pub mute: bool,
+  pub sound_log: Option<PathBuf>,
+  pub net_bind: Option<SocketAddr>,
+  pub net_peer: Option<SocketAddr>,
+  // Counting from 1, like the HUD, in the settings file and --net-player
+  pub net_player: usize,
+  pub input_delay: u32,
+}
+
+impl Default for Settings {
+  fn default() -> Settings {
+    Settings {
+      width: 1920,
+      height: 1080,
+      world_width: None,
+      world_height: None,
+      scale: 1,
+      fullscreen: false,
+      vsync: true,
+      seed: None,
+      players: 1,
+      friendly_fire: false,
+      headless: false,
+      ticks: None,
+      replay: None,
+      record: None,
+      load: None,
+      stats_dir: PathBuf::from(DEFAULT_STATS_DIR),
+      profile_trace: None,
+      rewind_seconds: 0.0,
+      scripts_dir: Some(PathBuf::from(DEFAULT_SCRIPTS_DIR)),
+      pools: PoolCaps::default(),
+      audio: AudioSettings::default(),
+      mute: false,
+      sound_log: None,
+      net_bind: None,
+      net_peer: None,
+      net_player: 1,
+      input_delay: 2,
+    }
+  }
+}
+
+impl Settings {
+  pub fn load(args: &[String]) -> Result<Settings, String> {
+    let mut settings = match config_path(args)? {
+      Some(path) => Settings::from_file(&path)?,
+      None if Path::new(DEFAULT_SETTINGS_PATH).is_file() => {
+        Settings::from_file(Path::new(DEFAULT_SETTINGS_PATH))?
+      }
+      None => Settings::default(),
+    };
+
+    settings.apply_args(args)?;
+
+    // Running from somewhere without the bundled scripts shouldn't be an
+    // error, just a game without enemies
+    if settings.scripts_dir == Some(PathBuf::from(DEFAULT_SCRIPTS_DIR))
+      && !Path::new(DEFAULT_SCRIPTS_DIR).is_dir()
+    {
+      settings.scripts_dir = None;
+    }
+
+    settings.validate()?;
+
+    // Both instances simulate both ships, whatever --players says
+    if settings.networked() {
+      settings.players = MAX_PLAYERS;
+    }
+
+    Ok(settings)
+  }
+
+  pub fn from_file(path: &Path) -> Result<Settings, String> {
+    let contents = fs::read_to_string(path)
+      .map_err(|e| format!("Unable to read settings file {}: {}", path.display(), e))?;
+
+    toml::from_str(&contents)
+      .map_err(|e| format!("Invalid settings file {}: {}", path.display(), e))
+  }
+
+  fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--config" => {
+          next_value(&mut args, arg)?;
+        }
+        "--resolution" => {
+          let (width, height) = parse_dimensions(arg, next_value(&mut args, arg)?)?;
+          self.width = width;
+          self.height = height;
+        }
+        "--world" => {
+          let (width, height) = parse_dimensions(arg, next_value(&mut args, arg)?)?;
+          self.world_width = Some(width);
+          self.world_height = Some(height);
+        }
+        "--scale" => self.scale = parse_value(arg, next_value(&mut args, arg)?)?,
+        "--fullscreen" => self.fullscreen = true,
+        "--windowed" => self.fullscreen = false,
+        "--vsync" => self.vsync = true,
+        "--no-vsync" => self.vsync = false,
+        "--seed" => self.seed = Some(parse_value(arg, next_value(&mut args, arg)?)?),
+        "--players" => self.players = parse_value(arg, next_value(&mut args, arg)?)?,
+        "--friendly-fire" => self.friendly_fire = true,
+        "--no-friendly-fire" => self.friendly_fire = false,
+        "--headless" => self.headless = true,
+        "--ticks" => self.ticks = Some(parse_value(arg, next_value(&mut args, arg)?)?),
+        "--replay" => self.replay = Some(PathBuf::from(next_value(&mut args, arg)?)),
+        "--record" => self.record = Some(PathBuf::from(next_value(&mut args, arg)?)),
+        "--load" => self.load = Some(PathBuf::from(next_value(&mut args, arg)?)),
+        "--stats-dir" => self.stats_dir = PathBuf::from(next_value(&mut args, arg)?),
+        "--profile-trace" => self.profile_trace = Some(PathBuf::from(next_value(&mut args, arg)?)),
+        "--rewind" => self.rewind_seconds = parse_value(arg, next_value(&mut args, arg)?)?,
+        "--scripts" => self.scripts_dir = Some(PathBuf::from(next_value(&mut args, arg)?)),
+        "--no-scripts" => self.scripts_dir = None,
+        "--pool-cap" => {
+          let (kind, cap) = parse_pool_cap(arg, next_value(&mut args, arg)?)?;
+          self.pools.set_cap(kind, cap);
+        }
+        "--volume" => {
+          let (target, volume) = parse_volume(arg, next_value(&mut args, arg)?)?;
+          self.audio.set_volume(target, volume);
+        }
+        "--mute" => self.mute = true,
+        "--sound-log" => self.sound_log = Some(PathBuf::from(next_value(&mut args, arg)?)),
+        "--net-bind" => self.net_bind = Some(parse_value(arg, next_value(&mut args, arg)?)?),
+        "--net-peer" => self.net_peer = Some(parse_value(arg, next_value(&mut args, arg)?)?),
+        "--net-player" => self.net_player = parse_value(arg, next_value(&mut args, arg)?)?,
+        "--input-delay" => self.input_delay = parse_value(arg, next_value(&mut args, arg)?)?,
+        "--help" | "-h" => return Err(USAGE.to_owned()),
+        _ => return Err(format!("Unknown option '{}'\n\n{}", arg, USAGE)),
+      }
+    }
+
+    Ok(())
+  }
+
+  fn validate(&self) -> Result<(), String> {
+    if self.width == 0 || self.height == 0 {
+      return Err(format!(
+        "Resolution must be at least 1x1, got {}x{}",
+        self.width, self.height
+      ));
+    }
+
+    if self.world_width == Some(0) || self.world_height == Some(0) {
+      return Err("World size must be at least 1x1".to_owned());
+    }
+
+    if self.scale == 0 {
+      return Err("Scale must be at least 1".to_owned());
+    }
+
+    if self.width % self.scale != 0 || self.height % self.scale != 0 {
+      return Err(format!(
+        "Resolution {}x{} is not divisible by scale {}",
+        self.width, self.height, self.scale
+      ));
+    }
+
+    if self.players == 0 || self.players > MAX_PLAYERS {
+      return Err(format!(
+        "Players must be between 1 and {}, got {}",
+        MAX_PLAYERS, self.players
+      ));
+    }
+
+    if self.rewind_seconds < 0.0 {
+      return Err(format!(
+        "Rewind must be at least 0 seconds, got {}",
+        self.rewind_seconds
+      ));
+    }
+
+    // Nothing else ends a headless run, and its stats only get written
+    // once it's over
+    if self.headless && self.replay.is_none() && self.ticks.is_none() {
+      return Err("--headless needs --replay or --ticks to know when to stop".to_owned());
+    }
+
+    if self.ticks.is_some() && !self.headless {
+      return Err("--ticks only works with --headless".to_owned());
+    }
+
+    if let Some(replay) = &self.replay {
+      if !replay.is_file() {
+        return Err(format!("Replay file {} does not exist", replay.display()));
+      }
+    }
+
+    if let Some(load) = &self.load {
+      if !load.is_file() {
+        return Err(format!("Snapshot file {} does not exist", load.display()));
+      }
+    }
+
+    if let Some(scripts_dir) = &self.scripts_dir {
+      if !scripts_dir.is_dir() {
+        return Err(format!(
+          "Scripts directory {} does not exist",
+          scripts_dir.display()
+        ));
+      }
+    }
+
+    if self
+      .audio
+      .all()
+      .iter()
+      .any(|&volume| volume < 0.0 || volume > 1.0)
+    {
+      return Err("Volumes must be between 0 and 1".to_owned());
+    }
+
+    // Only the null backend keeps a list of what it played
+    if self.sound_log.is_some() && !self.headless {
+      return Err("--sound-log only works with --headless".to_owned());
+    }
+
+    // Resuming from an earlier tick would leave ticks in the recording that
+    // the run no longer has
+    if self.rewind_seconds > 0.0 && self.record.is_some() {
+      return Err("--rewind can't be used with --record".to_owned());
+    }
+
+    // The replay would carry on from the later tick and feed the wrong
+    // inputs to the restored one
+    if self.rewind_seconds > 0.0 && self.replay.is_some() {
+      return Err("--rewind can't be used with --replay".to_owned());
+    }
+
+    if self.replay.is_some() && self.replay == self.record {
+      return Err("--replay and --record can't point at the same file".to_owned());
+    }
+
+    if self.net_bind.is_some() != self.net_peer.is_some() {
+      return Err("--net-bind and --net-peer have to be used together".to_owned());
+    }
+
+    if self.networked() {
+      if self.net_player == 0 || self.net_player > MAX_PLAYERS {
+        return Err(format!(
+          "Net player must be between 1 and {}, got {}",
+          MAX_PLAYERS, self.net_player
+        ));
+      }
+
+      // Both sides have to start from the same world
+      if self.seed.is_none() {
+        return Err("A networked game needs a --seed, the same on both sides".to_owned());
+      }
+
+      // Scrubbing back would leave the peer behind
+      if self.rewind_seconds > 0.0 {
+        return Err("--rewind can't be used in a networked game".to_owned());
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn networked(&self) -> bool {
+    self.net_bind.is_some() && self.net_peer.is_some()
+  }
+
+  pub fn game_settings(&self, seed: u64) -> GameSettings {
+    GameSettings {
+      width: self.width,
+      height: self.height,
+      world_width: self.world_width.unwrap_or(self.width),
+      world_height: self.world_height.unwrap_or(self.height),
+      scale: self.scale,
+      seed,
+      players: self.players,
+      friendly_fire: self.friendly_fire,
+    }
+  }
+
+  pub fn window_mode(&self) -> WindowMode {
+    WindowMode {
+      width: self.width,
+      height: self.height,
+      borderless: false,
+      fullscreen_type: if self.fullscreen {
+        FullscreenType::True
+      } else {
+        FullscreenType::Off
+      },
+      vsync: self.vsync,
+      min_width: 0,
+      min_height: 0,
+      max_width: 0,
+      max_height: 0,
+    }
+  }
+}
+
+// --config has to be known before the file is read, so it is picked out
+// ahead of the other options.
+fn config_path(args: &[String]) -> Result<Option<PathBuf>, String> {
+  let mut args = args.iter();
+
+  while let Some(arg) = args.next() {
+    if arg == "--config" {
+      let path = PathBuf::from(next_value(&mut args, arg)?);
+      if !path.is_file() {
+        return Err(format!("Settings file {} does not exist", path.display()));
+      }
+      return Ok(Some(path));
+    }
+  }
+
+  Ok(None)
+}
+
+fn next_value<'a, I: Iterator<Item = &'a String>>(
+  args: &mut I,
+  arg: &str,
+) -> Result<&'a str, String> {
+  args
+    .next()
+    .map(|value| value.as_str())
+    .ok_or_else(|| format!("Missing value for {}", arg))
+}
+
+fn parse_value<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+  value
+    .parse::<T>()
+    .map_err(|_| format!("Invalid value '{}' for {}", value, arg))
+}
+
+fn parse_dimensions(arg: &str, value: &str) -> Result<(u32, u32), String> {
+  let error = || {
+    format!(
+      "Invalid value '{}' for {}, expected WIDTHxHEIGHT (e.g. 1920x1080)",
+      value, arg
+    )
+  };
+
+  let mut parts = value.splitn(2, 'x');
+  let width = parts.next().and_then(|w| w.parse::<u32>().ok());
+  let height = parts.next().and_then(|h| h.parse::<u32>().ok());
+
+  match (width, height) {
+    (Some(width), Some(height)) => Ok((width, height)),
+    _ => Err(error()),
+  }
+}
+
+fn parse_pool_cap(arg: &str, value: &str) -> Result<(PoolKind, usize), String> {
+  let error = || {
+    format!(
+      "Invalid value '{}' for {}, expected KIND=N (e.g. projectile=128)",
+      value, arg
+    )
+  };
+
+  let mut parts = value.splitn(2, '=');
+  let kind = match parts.next() {
+    Some("projectile") => PoolKind::Projectile,
+    Some("explosion_line") => PoolKind::ExplosionLine,
+    Some("trail_particle") => PoolKind::TrailParticle,
+    _ => return Err(error()),
+  };
+  let cap = parts
+    .next()
+    .and_then(|cap| cap.parse::<usize>().ok())
+    .ok_or_else(error)?;
+
+  Ok((kind, cap))
+}
+
+fn parse_volume(arg: &str, value: &str) -> Result<(Option<SoundCategory>, f32), String> {
+  let error = || {
+    format!(
+      "Invalid value '{}' for {}, expected NAME=V (e.g. explosions=0.5)",
+      value, arg
+    )
+  };
+
+  let mut parts = value.splitn(2, '=');
+  let target = parts
+    .next()
+    .and_then(|name| parse_volume_target(name).ok())
+    .ok_or_else(error)?;
+  let volume = parts
+    .next()
+    .and_then(|volume| volume.parse::<f32>().ok())
+    .ok_or_else(error)?;
+
+  Ok((target, volume))
+}
@@ -0,0 +1,647 @@
+use crate::components::*;
+use crate::entities::*;
+use crate::resources::*;
+use crate::utils::*;
+
+use ggez::graphics::{self, DrawParam, Rect};
+use nalgebra::{Isometry2, Vector2};
+use ncollide2d::shape::{Ball, Cuboid, ShapeHandle};
+use ncollide2d::world::CollisionGroups;
+use nphysics2d::math::Velocity;
+use nphysics2d::object::{BodyHandle, BodyStatus, ColliderDesc, RigidBodyDesc};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::time::Duration;
+
+mod rewind;
+
+pub use rewind::*;
+
+pub const DEFAULT_SNAPSHOT_PATH: &str = "quicksave.json";
+
+// Bumped whenever the format changes, so old files are refused rather than
+// half loaded
+const SNAPSHOT_VERSION: u32 = 2;
+
+// ncollide supports collision groups 0..=29
+const COLLISION_GROUP_COUNT: usize = 30;
+
+// The whole game state between two ticks. Entities are stored without
+// their ids, they get fresh ones when restored.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+  pub version: u32,
+  pub settings: GameSettings,
+  pub spawn_info: SpawnInfoSnapshot,
+  pub score: Score,
+  pub rng: GameRng,
+  pub camera: Camera,
+  pub time_scale: TimeScale,
+  pub stats: RunStats,
+  pub particles: Particles,
+  #[serde(default)]
+  pub script_vars: ScriptVars,
+  pub entities: Vec<EntitySnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnInfoSnapshot {
+  pub since_last: Duration,
+  pub every: Duration,
+  pub count: u32,
+  pub max: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+  pub position: Option<PositionComponent>,
+  pub mesh: Option<MeshSnapshot>,
+  pub body: Option<BodySnapshot>,
+  pub controllable: Option<ControllableComponent>,
+  pub health: Option<HealthComponent>,
+  pub camera_focus: Option<CameraFocusComponent>,
+  pub trail: Option<TrailComponent>,
+  pub shooting: Option<ShootingComponent>,
+  pub garbage: Option<GarbageComponent>,
+  pub bounds: Option<BoundsComponent>,
+  pub explode_bounds: Option<ExplodeBoundsComponent>,
+  pub lifetime: Option<LifetimeComponent>,
+  pub tween: Option<TweenComponent>,
+  pub hit_flash: Option<HitFlashComponent>,
+  pub on_spawn: Option<OnSpawnComponent>,
+  pub on_death: Option<OnDeathComponent>,
+  pub pooled: Option<PooledComponent>,
+  pub owner: Option<OwnerComponent>,
+  pub god_mode: Option<GodModeComponent>,
+  pub power_up: Option<PowerUpComponent>,
+  pub particle_emitter: Option<ParticleEmitterComponent>,
+  pub script: Option<ScriptComponent>,
+}
+
+// The mesh itself is rebuilt from its key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshSnapshot {
+  pub key: MeshKey,
+  pub draw_param: DrawParamSnapshot,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrawParamSnapshot {
+  pub src: [f32; 4],
+  #[serde(with = "serde_point2")]
+  pub dest: graphics::Point2,
+  pub rotation: f32,
+  #[serde(with = "serde_point2")]
+  pub scale: graphics::Point2,
+  #[serde(with = "serde_point2")]
+  pub offset: graphics::Point2,
+  #[serde(with = "serde_point2")]
+  pub shear: graphics::Point2,
+  #[serde(with = "serde_option_color")]
+  pub color: Option<graphics::Color>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum BodyStatusSnapshot {
+  Disabled,
+  Static,
+  Dynamic,
+  Kinematic,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodySnapshot {
+  pub x: f32,
+  pub y: f32,
+  pub angle: f32,
+  pub linear_velocity: (f32, f32),
+  pub angular_velocity: f32,
+  pub status: BodyStatusSnapshot,
+  pub collider_type: Option<ColliderType>,
+  pub colliders: Vec<ColliderSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ShapeSnapshot {
+  Ball { radius: f32 },
+  Cuboid { half_width: f32, half_height: f32 },
+}
+
+// Colliders are rebuilt centred on their body, which is how every factory
+// makes them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColliderSnapshot {
+  pub shape: ShapeSnapshot,
+  pub membership: Vec<usize>,
+  pub whitelist: Vec<usize>,
+  pub blacklist: Vec<usize>,
+}
+
+pub fn save(world: &World, path: &Path) -> Result<(), String> {
+  let snapshot = capture(world)?;
+
+  if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+    fs::create_dir_all(dir).map_err(|e| format!("Unable to create {}: {}", dir.display(), e))?;
+  }
+  let file = File::create(path)
+    .map_err(|e| format!("Unable to create snapshot {}: {}", path.display(), e))?;
+
+  serde_json::to_writer(BufWriter::new(file), &snapshot)
+    .map_err(|e| format!("Unable to write snapshot {}: {}", path.display(), e))
+}
+
+pub fn load(world: &mut World, path: &Path) -> Result<(), String> {
+  let file =
+    File::open(path).map_err(|e| format!("Unable to read snapshot {}: {}", path.display(), e))?;
+  let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))
+    .map_err(|e| format!("Invalid snapshot {}: {}", path.display(), e))?;
+
+  if snapshot.version != SNAPSHOT_VERSION {
+    return Err(format!(
+      "Snapshot {} is version {}, expected {}",
+      path.display(),
+      snapshot.version,
+      SNAPSHOT_VERSION
+    ));
+  }
+
+  restore(world, snapshot)
+}
+
+// Should be called between ticks, once the world has been maintained
+pub fn capture(world: &World) -> Result<Snapshot, String> {
+  let spawn_info = world.read_resource::<SpawnInfo>();
+  let physics = world.read_resource::<PhysicsSim>();
+  let mut entities = vec![];
+
+  for entity in world.entities().join() {
+    // Parked pooled entities are rebuilt by their factories as needed
+    if component::<PooledComponent>(world, entity).is_some()
+      && component::<PositionComponent>(world, entity).is_none()
+    {
+      continue;
+    }
+
+    let mesh = world
+      .read_storage::<MeshComponent>()
+      .get(entity)
+      .map(|mesh| MeshSnapshot {
+        key: mesh.key,
+        draw_param: DrawParamSnapshot::from(&mesh.draw_param),
+      });
+
+    let body = match world.read_storage::<RigidBodyComponent>().get(entity) {
+      Some(rigid_body) => Some(capture_body(&physics, rigid_body.handle)?),
+      None => None,
+    };
+
+    entities.push(EntitySnapshot {
+      position: component(world, entity),
+      mesh,
+      body,
+      controllable: component(world, entity),
+      health: component(world, entity),
+      camera_focus: component(world, entity),
+      trail: component(world, entity),
+      shooting: component(world, entity),
+      garbage: component(world, entity),
+      bounds: component(world, entity),
+      explode_bounds: component(world, entity),
+      lifetime: component(world, entity),
+      tween: component(world, entity),
+      hit_flash: component(world, entity),
+      on_spawn: component(world, entity),
+      on_death: component(world, entity),
+      pooled: component(world, entity),
+      owner: component(world, entity),
+      god_mode: component(world, entity),
+      power_up: component(world, entity),
+      particle_emitter: component(world, entity),
+      script: component(world, entity),
+    });
+  }
+
+  Ok(Snapshot {
+    version: SNAPSHOT_VERSION,
+    settings: world.read_resource::<GameSettings>().clone(),
+    spawn_info: SpawnInfoSnapshot {
+      since_last: spawn_info.ammo_since_last,
+      every: spawn_info.ammo_every,
+      count: spawn_info.ammo_count,
+      max: spawn_info.ammo_max,
+    },
+    score: world.read_resource::<Score>().clone(),
+    rng: world.read_resource::<GameRng>().clone(),
+    camera: world.read_resource::<Camera>().clone(),
+    time_scale: world.read_resource::<TimeScale>().clone(),
+    stats: world.read_resource::<RunStats>().clone(),
+    particles: world.read_resource::<Particles>().clone(),
+    script_vars: world.read_resource::<ScriptVars>().clone(),
+    entities,
+  })
+}
+
+// Throws away every entity and body, and rebuilds the world from the
+// snapshot with a fresh physics world. A snapshot that can't be restored
+// is refused before anything is thrown away.
+pub fn restore(world: &mut World, snapshot: Snapshot) -> Result<(), String> {
+  validate(world, &snapshot)?;
+
+  world.maintain();
+  world.delete_all();
+  world.maintain();
+
+  *world.write_resource::<PhysicsSim>() = PhysicsSim::default();
+  world.write_resource::<Pools>().clear();
+
+  // The screen belongs to this session, only the playfield comes along
+  {
+    let mut settings = world.write_resource::<GameSettings>();
+    settings.world_width = snapshot.settings.world_width;
+    settings.world_height = snapshot.settings.world_height;
+    settings.seed = snapshot.settings.seed;
+  }
+
+  *world.write_resource::<SpawnInfo>() = SpawnInfo {
+    ammo_since_last: snapshot.spawn_info.since_last,
+    ammo_every: snapshot.spawn_info.every,
+    ammo_count: snapshot.spawn_info.count,
+    ammo_max: snapshot.spawn_info.max,
+  };
+  *world.write_resource::<Score>() = snapshot.score;
+  *world.write_resource::<GameRng>() = snapshot.rng;
+  *world.write_resource::<Camera>() = snapshot.camera;
+  *world.write_resource::<TimeScale>() = snapshot.time_scale;
+  *world.write_resource::<RunStats>() = snapshot.stats;
+  *world.write_resource::<Particles>() = snapshot.particles;
+  *world.write_resource::<ScriptVars>() = snapshot.script_vars;
+
+  for entity_snapshot in snapshot.entities {
+    restore_entity(world, entity_snapshot)?;
+  }
+
+  Ok(())
+}
+
+// Everything that could stop a restore half way through
+fn validate(world: &World, snapshot: &Snapshot) -> Result<(), String> {
+  let mut pooled: HashMap<PoolKind, usize> = HashMap::new();
+
+  for entity in snapshot.entities.iter() {
+    if let Some(entity_pooled) = &entity.pooled {
+      *pooled.entry(entity_pooled.kind).or_insert(0) += 1;
+    }
+
+    for collider in entity.body.iter().flat_map(|body| body.colliders.iter()) {
+      let mut groups = collider
+        .membership
+        .iter()
+        .chain(collider.whitelist.iter())
+        .chain(collider.blacklist.iter());
+      if let Some(group) = groups.find(|group| **group >= COLLISION_GROUP_COUNT) {
+        return Err(format!("collision group {} is out of range", group));
+      }
+    }
+  }
+
+  // Live pooled entities still count against their pool's cap
+  let pools = world.read_resource::<Pools>();
+  for (kind, count) in pooled {
+    if count > pools.cap(kind) {
+      return Err(format!(
+        "{} {:?} entities is more than the pool's cap of {}",
+        count,
+        kind,
+        pools.cap(kind)
+      ));
+    }
+  }
+
+  Ok(())
+}
+
+fn restore_entity(world: &mut World, snapshot: EntitySnapshot) -> Result<(), String> {
+  let entity = world.create_entity().build();
+
+  if let Some(body) = snapshot.body {
+    let handle = {
+      let mut physics = world.write_resource::<PhysicsSim>();
+      restore_body(&mut physics, entity, &body)
+    };
+    insert(world, entity, Some(RigidBodyComponent { handle }));
+  }
+
+  // Checked against the caps by validate()
+  if let Some(pooled) = &snapshot.pooled {
+    if world
+      .write_resource::<Pools>()
+      .acquire(pooled.kind)
+      .is_none()
+    {
+      return Err(format!("too many {:?} entities for the pool", pooled.kind));
+    }
+  }
+
+  let mesh = snapshot.mesh.map(|mesh| MeshComponent {
+    key: mesh.key,
+    draw_param: mesh.draw_param.into(),
+  });
+
+  insert(world, entity, snapshot.position);
+  insert(world, entity, mesh);
+  insert(world, entity, snapshot.controllable);
+  insert(world, entity, snapshot.health);
+  insert(world, entity, snapshot.camera_focus);
+  insert(world, entity, snapshot.trail);
+  insert(world, entity, snapshot.shooting);
+  insert(world, entity, snapshot.garbage);
+  insert(world, entity, snapshot.bounds);
+  insert(world, entity, snapshot.explode_bounds);
+  insert(world, entity, snapshot.lifetime);
+  insert(world, entity, snapshot.tween);
+  insert(world, entity, snapshot.hit_flash);
+  insert(world, entity, snapshot.on_spawn);
+  insert(world, entity, snapshot.on_death);
+  insert(world, entity, snapshot.pooled);
+  insert(world, entity, snapshot.owner);
+  insert(world, entity, snapshot.god_mode);
+  insert(world, entity, snapshot.power_up);
+  insert(world, entity, snapshot.particle_emitter);
+  insert(world, entity, snapshot.script);
+
+  Ok(())
+}
+
+fn capture_body(physics: &PhysicsSim, handle: BodyHandle) -> Result<BodySnapshot, String> {
+  let body = physics
+    .world
+    .rigid_body(handle)
+    .ok_or_else(|| format!("missing rigid body {:?}", handle))?;
+
+  let position = body.position();
+  let velocity = body.velocity();
+
+  let status = match body.status() {
+    BodyStatus::Disabled => BodyStatusSnapshot::Disabled,
+    BodyStatus::Static => BodyStatusSnapshot::Static,
+    BodyStatus::Dynamic => BodyStatusSnapshot::Dynamic,
+    BodyStatus::Kinematic => BodyStatusSnapshot::Kinematic,
+  };
+
+  let colliders = physics
+    .body_colliders(handle)
+    .iter()
+    .filter_map(|collider| physics.world.collider(*collider))
+    .map(|collider| {
+      let shape = collider.shape();
+      let shape = if let Some(ball) = shape.as_shape::<Ball<f32>>() {
+        ShapeSnapshot::Ball {
+          radius: ball.radius(),
+        }
+      } else if let Some(cuboid) = shape.as_shape::<Cuboid<f32>>() {
+        let half = cuboid.half_extents();
+        ShapeSnapshot::Cuboid {
+          half_width: half.x,
+          half_height: half.y,
+        }
+      } else {
+        return Err(format!("rigid body {:?} has an unsupported shape", handle));
+      };
+
+      let groups = collider.collision_groups();
+
+      Ok(ColliderSnapshot {
+        shape,
+        membership: groups_where(|group| groups.is_member_of(group)),
+        whitelist: groups_where(|group| groups.is_group_whitelisted(group)),
+        blacklist: groups_where(|group| groups.is_group_blacklisted(group)),
+      })
+    })
+    .collect::<Result<Vec<ColliderSnapshot>, String>>()?;
+
+  Ok(BodySnapshot {
+    x: position.translation.vector.x,
+    y: position.translation.vector.y,
+    angle: position.rotation.angle(),
+    linear_velocity: (velocity.linear.x, velocity.linear.y),
+    angular_velocity: velocity.angular,
+    status,
+    collider_type: physics.collider_type(handle),
+    colliders,
+  })
+}
+
+fn restore_body(physics: &mut PhysicsSim, entity: Entity, body: &BodySnapshot) -> BodyHandle {
+  let collider_descs: Vec<ColliderDesc<f32>> = body
+    .colliders
+    .iter()
+    .map(|collider| {
+      let shape = match collider.shape {
+        ShapeSnapshot::Ball { radius } => ShapeHandle::new(Ball::new(radius)),
+        ShapeSnapshot::Cuboid {
+          half_width,
+          half_height,
+        } => ShapeHandle::new(Cuboid::new(Vector2::new(half_width, half_height))),
+      };
+
+      let collision_groups = CollisionGroups::new()
+        .with_membership(&collider.membership)
+        .with_whitelist(&collider.whitelist)
+        .with_blacklist(&collider.blacklist);
+
+      ColliderDesc::new(shape).collision_groups(collision_groups)
+    })
+    .collect();
+
+  let status = match body.status {
+    BodyStatusSnapshot::Disabled => BodyStatus::Disabled,
+    BodyStatusSnapshot::Static => BodyStatus::Static,
+    BodyStatusSnapshot::Dynamic => BodyStatus::Dynamic,
+    BodyStatusSnapshot::Kinematic => BodyStatus::Kinematic,
+  };
+
+  let mut rigid_body_desc = RigidBodyDesc::new()
+    .position(Isometry2::new(Vector2::new(body.x, body.y), body.angle))
+    .velocity(Velocity::new(
+      Vector2::new(body.linear_velocity.0, body.linear_velocity.1),
+      body.angular_velocity,
+    ))
+    .status(status);
+
+  for collider_desc in collider_descs.iter() {
+    rigid_body_desc = rigid_body_desc.collider(collider_desc);
+  }
+
+  let handle = rigid_body_desc.build(&mut physics.world).handle();
+  physics.register_body(handle, entity, body.collider_type);
+  handle
+}
+
+impl<'a> From<&'a DrawParam> for DrawParamSnapshot {
+  fn from(draw_param: &DrawParam) -> DrawParamSnapshot {
+    DrawParamSnapshot {
+      src: [
+        draw_param.src.x,
+        draw_param.src.y,
+        draw_param.src.w,
+        draw_param.src.h,
+      ],
+      dest: draw_param.dest,
+      rotation: draw_param.rotation,
+      scale: draw_param.scale,
+      offset: draw_param.offset,
+      shear: draw_param.shear,
+      color: draw_param.color,
+    }
+  }
+}
+
+impl From<DrawParamSnapshot> for DrawParam {
+  fn from(snapshot: DrawParamSnapshot) -> DrawParam {
+    let [x, y, w, h] = snapshot.src;
+    DrawParam {
+      src: Rect::new(x, y, w, h),
+      dest: snapshot.dest,
+      rotation: snapshot.rotation,
+      scale: snapshot.scale,
+      offset: snapshot.offset,
+      shear: snapshot.shear,
+      color: snapshot.color,
+    }
+  }
+}
+
+fn groups_where<F: Fn(usize) -> bool>(test: F) -> Vec<usize> {
+  (0..COLLISION_GROUP_COUNT)
+    .filter(|group| test(*group))
+    .collect()
+}
+
+fn component<C: Component + Clone>(world: &World, entity: Entity) -> Option<C> {
+  world.read_storage::<C>().get(entity).cloned()
+}
+
+fn insert<C: Component>(world: &World, entity: Entity, component: Option<C>) {
+  if let Some(component) = component {
+    world
+      .write_storage::<C>()
+      .insert(entity, component)
+      .unwrap();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::settings::Settings;
+  use crate::{create_world, fixed_tick, run_tick, set_update_time};
+  use serde_json::Value;
+
+  fn settings() -> Settings {
+    Settings {
+      seed: Some(7),
+      scripts_dir: None,
+      ..Settings::default()
+    }
+  }
+
+  // Angles can lose their last bit going through a body's rotation
+  fn close(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+      (Value::Number(a), Value::Number(b)) => {
+        (a.as_f64().unwrap() - b.as_f64().unwrap()).abs() <= 1e-4
+      }
+      (Value::Array(a), Value::Array(b)) => {
+        a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| close(a, b))
+      }
+      (Value::Object(a), Value::Object(b)) => {
+        a.len() == b.len()
+          && a
+            .iter()
+            .all(|(key, a)| b.get(key).map_or(false, |b| close(a, b)))
+      }
+      _ => a == b,
+    }
+  }
+
+  #[test]
+  fn round_trip() {
+    let (mut world, mut dispatcher) = create_world(&settings());
+    {
+      let entities = world.entities();
+      let lazy = world.read_resource::<LazyUpdate>();
+      let game_settings = world.read_resource::<GameSettings>();
+      let mut physics = world.write_resource::<PhysicsSim>();
+      let mut pools = world.write_resource::<Pools>();
+      let mut rng = world.write_resource::<GameRng>();
+
+      let position = PositionComponent {
+        x: 200.0,
+        y: 200.0,
+        angle: 0.3,
+      };
+      create_projectile(
+        &entities,
+        &lazy,
+        &game_settings,
+        &mut physics,
+        &mut pools,
+        Some(0),
+        position,
+      );
+      create_death_explosion(
+        &entities,
+        &lazy,
+        &mut physics,
+        &mut pools,
+        &mut rng,
+        400.0,
+        300.0,
+      );
+    }
+
+    // A few ticks in, the explosion lines are part way through their tweens
+    for _ in 0..5 {
+      set_update_time(&mut world, fixed_tick());
+      run_tick(&mut world, &mut dispatcher);
+    }
+
+    assert!((
+      &world.read_storage::<PooledComponent>(),
+      &world.read_storage::<PositionComponent>()
+    )
+      .join()
+      .any(|(pooled, _)| pooled.kind == PoolKind::Projectile));
+    assert!(world
+      .read_storage::<TweenComponent>()
+      .join()
+      .any(|tween| tween.elapsed > Duration::from_millis(0) && !tween.completed));
+
+    let before = capture(&world).unwrap();
+    let json = serde_json::to_string(&before).unwrap();
+
+    let (mut restored, _) = create_world(&settings());
+    restore(&mut restored, serde_json::from_str(&json).unwrap()).unwrap();
+    restored.maintain();
+    let after = capture(&restored).unwrap();
+
+    let before = serde_json::to_value(&before).unwrap();
+    let mut after = serde_json::to_value(&after).unwrap();
+
+    // Restored entities get fresh ids, so they can come back in any order
+    let mut remaining = after["entities"].as_array().unwrap().clone();
+    for entity in before["entities"].as_array().unwrap() {
+      let found = remaining.iter().position(|other| close(entity, other));
+      assert!(found.is_some(), "entity didn't survive: {}", entity);
+      remaining.remove(found.unwrap());
+    }
+    assert!(remaining.is_empty(), "extra entities: {:?}", remaining);
+
+    after["entities"] = before["entities"].clone();
+    assert!(close(&before, &after), "{} != {}", before, after);
+  }
+}
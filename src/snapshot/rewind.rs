@@ -0,0 +1,113 @@
+use super::*;
+
+use ggez::{Context, GameResult};
+use std::collections::VecDeque;
+
+// The last few seconds of the world, one snapshot per tick. While
+// scrubbing the simulation is paused, and resuming carries on from
+// whichever tick is showing, forgetting the ticks after it.
+pub struct RewindBuffer {
+  span: Duration,
+  // Each tick's snapshot, taken after it ran, with the time it covered
+  frames: VecDeque<(Duration, Snapshot)>,
+  total: Duration,
+  // The frame being shown, None while the simulation is running
+  cursor: Option<usize>,
+}
+
+impl RewindBuffer {
+  pub fn new(seconds: f32) -> RewindBuffer {
+    RewindBuffer {
+      span: Duration::from_millis((seconds * 1000.0) as u64),
+      frames: VecDeque::new(),
+      total: Duration::from_millis(0),
+      cursor: None,
+    }
+  }
+
+  pub fn scrubbing(&self) -> bool {
+    self.cursor.is_some()
+  }
+
+  // Call after every tick, once the world has been maintained
+  pub fn record(&mut self, world: &World, dt: Duration) {
+    let snapshot = match capture(world) {
+      Ok(snapshot) => snapshot,
+      Err(e) => {
+        eprintln!("Unable to record rewind frame: {}", e);
+        return;
+      }
+    };
+
+    self.frames.push_back((dt, snapshot));
+    self.total += dt;
+
+    while self.total > self.span && self.frames.len() > 1 {
+      let (dt, _) = self.frames.pop_front().unwrap();
+      self.total -= dt;
+    }
+  }
+
+  // Pauses on the latest tick, which is what the world already shows
+  pub fn start(&mut self) {
+    if !self.frames.is_empty() {
+      self.cursor = Some(self.frames.len() - 1);
+    }
+  }
+
+  // Moves `ticks` back (negative) or forward through the buffer and shows
+  // that tick
+  pub fn step(&mut self, world: &mut World, ticks: isize) -> Result<(), String> {
+    let cursor = match self.cursor {
+      Some(cursor) => cursor,
+      None => return Ok(()),
+    };
+
+    let last = self.frames.len() as isize - 1;
+    let target = (cursor as isize + ticks).max(0).min(last) as usize;
+    if target == cursor {
+      return Ok(());
+    }
+
+    restore(world, self.frames[target].1.clone())?;
+    self.cursor = Some(target);
+    Ok(())
+  }
+
+  // Carries on from the tick being shown
+  pub fn resume(&mut self) {
+    if let Some(cursor) = self.cursor.take() {
+      while self.frames.len() > cursor + 1 {
+        let (dt, _) = self.frames.pop_back().unwrap();
+        self.total -= dt;
+      }
+    }
+  }
+
+  pub fn draw(&self, ctx: &mut Context, font: &graphics::Font, height: f32) -> GameResult<()> {
+    let cursor = match self.cursor {
+      Some(cursor) => cursor,
+      None => return Ok(()),
+    };
+
+    // How far behind the latest recorded tick we are
+    let behind: Duration = self.frames.iter().skip(cursor + 1).map(|(dt, _)| *dt).sum();
+    let behind = behind.as_millis() as f32 / 1000.0;
+
+    let label = format!(
+      "REWIND {}/{}  -{:.2}s  (Left/Right to scrub, F3 to resume)",
+      cursor + 1,
+      self.frames.len(),
+      behind
+    );
+
+    graphics::set_color(ctx, graphics::WHITE)?;
+    let text = graphics::Text::new(ctx, &label, font)?;
+    graphics::draw(
+      ctx,
+      &text,
+      graphics::Point2::new(4.0, height - text.height() as f32 - 4.0),
+      0.0,
+    )
+  }
+}
@@ -0,0 +1,166 @@
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use specs::world::*;
+use specs::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::components::*;
+use crate::resources::*;
+
+// Turns this tick's game events into sounds for the audio backend. Which
+// sounds play, and at what pitch, only depends on the events, so a
+// headless run with the null backend hears exactly what a window would.
+// Entities that make a sound when they appear say so with an OnSpawn
+// Effect::Sound, which reaches here as a PlaySound event.
+#[derive(Default)]
+pub struct AudioSystem {
+  reader: Option<ReaderId<GameEvent>>,
+  // Kept apart from GameRng so sounds can't change the simulation
+  rng: Option<Pcg32>,
+  clock: Duration,
+  // When each sound's current plays finish
+  playing: HashMap<&'static str, Vec<Duration>>,
+}
+
+impl<'a> System<'a> for AudioSystem {
+  type SystemData = (
+    Read<'a, UpdateTime>,
+    Read<'a, AudioSettings>,
+    Read<'a, GameEvents>,
+    Write<'a, SoundQueue>,
+  );
+
+  fn run(&mut self, (update_time, audio_settings, game_events, mut queue): Self::SystemData) {
+    self.clock += update_time.0;
+    let clock = self.clock;
+
+    for ends in self.playing.values_mut() {
+      ends.retain(|end| *end > clock);
+    }
+
+    for event in game_events.read(&mut self.reader.as_mut().unwrap()) {
+      let name = match event {
+        GameEvent::PickupCollected {
+          kind: ColliderType::Ammo,
+          ..
+        } => "ammo_pickup",
+        GameEvent::EnemyHit { .. } => "enemy_hit",
+        GameEvent::DeathExplosion { .. } => "death_explosion",
+        GameEvent::OutOfBoundsExplosion { .. } => "out_of_bounds_explosion",
+        GameEvent::PlaySound { name, .. } => name.as_str(),
+        _ => continue,
+      };
+
+      let sound = match sound_def(name) {
+        Some(sound) => sound,
+        None => continue,
+      };
+
+      let ends = self.playing.entry(sound.name).or_insert_with(Vec::new);
+      if ends.len() >= sound.max_concurrent {
+        continue;
+      }
+
+      let volume =
+        sound.volume * audio_settings.volume(Some(sound.category)) * audio_settings.volume(None);
+      if volume <= 0.0 {
+        continue;
+      }
+
+      let rng = self.rng.as_mut().unwrap();
+      let pitch = if sound.pitch_variance > 0.0 {
+        1.0 + rng.gen_range(-sound.pitch_variance, sound.pitch_variance)
+      } else {
+        1.0
+      };
+
+      ends.push(clock + sound.length);
+      queue.0.push(SoundPlay {
+        name: sound.name.to_owned(),
+        at: clock,
+        volume,
+        pitch,
+      });
+    }
+  }
+
+  fn setup(&mut self, res: &mut Resources) {
+    Self::SystemData::setup(res);
+    self.reader = Some(res.fetch_mut::<GameEvents>().register_reader());
+    let seed = res.fetch::<GameSettings>().seed;
+    self.rng = Some(Pcg32::seed_from_u64(seed));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::settings::Settings;
+
+  // A world with just what AudioSystem reads, and the system set up in it
+  fn world(audio_settings: AudioSettings) -> (World, AudioSystem) {
+    let mut world = World::new();
+    world.add_resource(Settings::default().game_settings(7));
+    world.add_resource(audio_settings);
+    world.add_resource(UpdateTime(Duration::from_millis(16)));
+
+    let mut system = AudioSystem::default();
+    System::setup(&mut system, &mut world.res);
+    (world, system)
+  }
+
+  fn tick(world: &World, system: &mut AudioSystem, events: Vec<GameEvent>) -> Vec<String> {
+    world.write_resource::<GameEvents>().iter_write(events);
+    system.run_now(&world.res);
+    let played = world
+      .write_resource::<SoundQueue>()
+      .0
+      .drain(..)
+      .collect::<Vec<_>>();
+    played.into_iter().map(|sound| sound.name).collect()
+  }
+
+  fn shot() -> GameEvent {
+    GameEvent::PlaySound {
+      name: "shoot".to_owned(),
+      x: 0.0,
+      y: 0.0,
+    }
+  }
+
+  #[test]
+  fn plays_shots_and_pickups() {
+    let (world, mut system) = world(AudioSettings::default());
+    let pickup = GameEvent::PickupCollected {
+      kind: ColliderType::Ammo,
+      player: Some(0),
+      x: 0.0,
+      y: 0.0,
+    };
+
+    assert_eq!(tick(&world, &mut system, vec![shot()]), vec!["shoot"]);
+    assert_eq!(tick(&world, &mut system, vec![pickup]), vec!["ammo_pickup"]);
+  }
+
+  #[test]
+  fn drops_plays_past_the_cap() {
+    let (world, mut system) = world(AudioSettings::default());
+    let cap = sound_def("shoot").unwrap().max_concurrent;
+
+    let played = tick(&world, &mut system, (0..cap + 2).map(|_| shot()).collect());
+    assert_eq!(played.len(), cap);
+
+    // Still playing a tick later, so there's no room yet
+    assert!(tick(&world, &mut system, vec![shot()]).is_empty());
+  }
+
+  #[test]
+  fn skips_muted_categories() {
+    let mut audio_settings = AudioSettings::default();
+    audio_settings.set_volume(Some(SoundCategory::Weapons), 0.0);
+    let (world, mut system) = world(audio_settings);
+
+    assert!(tick(&world, &mut system, vec![shot()]).is_empty());
+  }
+}
@@ -11,7 +11,7 @@ pub struct BoundsSystem;
 impl<'a> System<'a> for BoundsSystem {
   type SystemData = (
     Read<'a, EntitiesRes>,
-    Read<'a, LazyUpdate>,
+    Write<'a, EffectEvents>,
     ReadStorage<'a, BoundsComponent>,
     ReadStorage<'a, ExplodeBoundsComponent>,
     ReadStorage<'a, PositionComponent>,
@@ -20,7 +20,7 @@ impl<'a> System<'a> for BoundsSystem {
 
   fn run(
     &mut self,
-    (entities, lazy, bounds, explode_bounds, position, mut garbage): Self::SystemData,
+    (entities, mut effect_events, bounds, explode_bounds, position, mut garbage): Self::SystemData,
   ) {
     for (entity, bounds, position, garbage) in (&entities, &bounds, &position, &mut garbage).join()
     {
@@ -29,6 +29,7 @@ impl<'a> System<'a> for BoundsSystem {
 
       if out_of_bounds_x || out_of_bounds_y {
         garbage.is_alive = false;
+        garbage.culled = true;
 
         let explode_bounds_component: Option<&ExplodeBoundsComponent> = explode_bounds.get(entity);
         if let Some(_) = explode_bounds_component {
@@ -37,7 +38,10 @@ impl<'a> System<'a> for BoundsSystem {
             y: position.y,
             angle: 0.0,
           };
-          create_out_of_bounds_explosion(&entities, &lazy, position_component);
+          effect_events.single_write(EffectEvent {
+            effect: Effect::Spawn(Prefab::OutOfBoundsExplosion),
+            position: position_component,
+          });
         }
       }
     }
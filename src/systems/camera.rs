@@ -0,0 +1,76 @@
+use specs::world::*;
+use specs::*;
+
+use crate::components::*;
+use crate::resources::*;
+
+// Fraction of the distance to the focus covered per second
+const FOLLOW_SPEED: f32 = 5.0;
+
+#[derive(Default)]
+pub struct CameraSystem {
+  reader: Option<ReaderId<GameEvent>>,
+}
+
+impl<'a> System<'a> for CameraSystem {
+  type SystemData = (
+    Read<'a, UpdateTime>,
+    Read<'a, GameSettings>,
+    Read<'a, GameEvents>,
+    Write<'a, Camera>,
+    ReadStorage<'a, CameraFocusComponent>,
+    ReadStorage<'a, PositionComponent>,
+  );
+
+  fn run(
+    &mut self,
+    (update_time, game_settings, game_events, mut camera, focus, position): Self::SystemData,
+  ) {
+    let dt = update_time.0.subsec_nanos() as f32 / 1_000_000_000.0;
+
+    for event in game_events.read(&mut self.reader.as_mut().unwrap()) {
+      match event {
+        GameEvent::PlayerHit { .. } => camera.add_trauma(0.6),
+        GameEvent::DeathExplosion { .. } => camera.add_trauma(0.3),
+        GameEvent::OutOfBoundsExplosion { .. } => camera.add_trauma(0.1),
+        GameEvent::PickupCollected { .. } => camera.add_trauma(0.15),
+        GameEvent::EnemyHit { .. } => camera.add_trauma(0.1),
+        _ => (),
+      }
+    }
+
+    // With more than one focus, e.g. in co-op, follow the point between them
+    let focused: Vec<&PositionComponent> = (&focus, &position).join().map(|(_, p)| p).collect();
+    if !focused.is_empty() {
+      let count = focused.len() as f32;
+      let x = focused.iter().map(|p| p.x).sum::<f32>() / count;
+      let y = focused.iter().map(|p| p.y).sum::<f32>() / count;
+
+      let t = (FOLLOW_SPEED * dt).min(1.0);
+      camera.x += (x - camera.x) * t;
+      camera.y += (y - camera.y) * t;
+    }
+
+    // Keep the view inside the world, or centred on it when the world is
+    // smaller than the view.
+    let half_width = game_settings.width as f32 / game_settings.scale as f32 / 2.0 / camera.zoom;
+    let half_height = game_settings.height as f32 / game_settings.scale as f32 / 2.0 / camera.zoom;
+    camera.x = clamp_axis(camera.x, half_width, game_settings.world_width as f32);
+    camera.y = clamp_axis(camera.y, half_height, game_settings.world_height as f32);
+
+    camera.update_shake(dt);
+  }
+
+  fn setup(&mut self, res: &mut Resources) {
+    Self::SystemData::setup(res);
+    self.reader = Some(res.fetch_mut::<GameEvents>().register_reader());
+  }
+}
+
+fn clamp_axis(value: f32, half_view: f32, world_size: f32) -> f32 {
+  if world_size <= half_view * 2.0 {
+    world_size / 2.0
+  } else {
+    value.max(half_view).min(world_size - half_view)
+  }
+}
@@ -8,6 +8,13 @@ use specs::world::*;
 use specs::*;
 use std::time::Instant;
 
+// Damage a ship takes from another player's projectile
+const FRIENDLY_FIRE_DAMAGE: f32 = 10.0;
+// Damage an enemy takes from any player's projectile
+const ENEMY_HIT_DAMAGE: f32 = 10.0;
+// Damage a ship takes each time it runs into an enemy
+const ENEMY_CONTACT_DAMAGE: f32 = 20.0;
+
 #[derive(Default)]
 pub struct CollisionSystem {
   reader: Option<ReaderId<CollisionEvent>>,
@@ -15,22 +22,55 @@ pub struct CollisionSystem {
 
 impl<'a> System<'a> for CollisionSystem {
   type SystemData = (
-    Read<'a, EntitiesRes>,
-    Read<'a, LazyUpdate>,
-    Write<'a, PhysicsSim>,
     Read<'a, CollisionEvents>,
+    Write<'a, GameEvents>,
+    Write<'a, DamageEvents>,
+    ReadStorage<'a, ControllableComponent>,
     WriteStorage<'a, GarbageComponent>,
   );
 
   fn run(
     &mut self,
-    (entities, lazy, mut physics, collision_events, mut garbage): Self::SystemData,
+    (collision_events, mut game_events, mut damage_events, controllable, mut garbage): Self::SystemData,
   ) {
     for event in collision_events.read(&mut self.reader.as_mut().unwrap()) {
+      if event.phase != ContactPhase::Started {
+        continue;
+      }
+
       match event.collision_type {
-        CollisionType::PlayerAmmo { player: _, ammo } => {
+        CollisionType::PlayerAmmo { player, ammo } => {
           garbage.get_mut(ammo).map(|g| g.is_alive = false);
-          create_death_explosion(&entities, &lazy, &mut physics, event.x, event.y)
+          game_events.single_write(GameEvent::PickupCollected {
+            kind: ColliderType::Ammo,
+            player: controllable.get(player).map(|c| c.player),
+            x: event.x,
+            y: event.y,
+          });
+        }
+        CollisionType::ProjectilePlayer { projectile, player } => {
+          garbage.get_mut(projectile).map(|g| g.is_alive = false);
+          damage_events.single_write(DamageEvent {
+            entity: player,
+            amount: FRIENDLY_FIRE_DAMAGE,
+          });
+        }
+        CollisionType::ProjectileEnemy { projectile, enemy } => {
+          garbage.get_mut(projectile).map(|g| g.is_alive = false);
+          damage_events.single_write(DamageEvent {
+            entity: enemy,
+            amount: ENEMY_HIT_DAMAGE,
+          });
+          game_events.single_write(GameEvent::EnemyHit {
+            x: event.x,
+            y: event.y,
+          });
+        }
+        CollisionType::PlayerEnemy { player, .. } => {
+          damage_events.single_write(DamageEvent {
+            entity: player,
+            amount: ENEMY_CONTACT_DAMAGE,
+          });
         }
       }
     }
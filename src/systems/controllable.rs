@@ -7,20 +7,32 @@ use crate::components::*;
 use crate::entities::*;
 use crate::resources::*;
 
+// Radians per tick at full stick deflection
+const TURN_RATE: f32 = 0.05;
+const BASE_VELOCITY: f32 = 250.0;
+const BOOST_FACTOR: f32 = 0.5;
+const BRAKE_FACTOR: f32 = 0.5;
+
 pub struct ControllableSystem;
 
 impl<'a> System<'a> for ControllableSystem {
   type SystemData = (
-    Read<'a, EntitiesRes>,
-    Read<'a, LazyUpdate>,
-    Read<'a, Input>,
+    Read<'a, Inputs>,
     Write<'a, PhysicsSim>,
+    Write<'a, EffectEvents>,
     WriteStorage<'a, RigidBodyComponent>,
-    ReadStorage<'a, ControllableComponent>,
+    WriteStorage<'a, ControllableComponent>,
   );
 
-  fn run(&mut self, (entities, lazy, input, mut physics, mut rb, ctrled): Self::SystemData) {
-    (&mut rb, &ctrled).join().for_each(|(rb, _ctrled)| {
+  fn run(
+    &mut self,
+    (inputs, mut physics, mut effect_events, mut rb, mut ctrled): Self::SystemData,
+  ) {
+    let idle = Input::default();
+
+    (&mut rb, &mut ctrled).join().for_each(|(rb, ctrled)| {
+      // A ship without an input, e.g. from a co-op snapshot, flies straight
+      let input = inputs.0.get(ctrled.player).unwrap_or(&idle);
       let body: &mut RigidBody<f32> = physics
         .world
         .rigid_body_mut(rb.handle)
@@ -28,21 +40,25 @@ impl<'a> System<'a> for ControllableSystem {
 
       let pos = body.position().translation;
       let angle = body.position().rotation.angle();
-      let v = 250.0;
+      ctrled.boost = input.boost();
+      ctrled.brake = input.brake();
+
+      let v = BASE_VELOCITY * (1.0 + BOOST_FACTOR * ctrled.boost - BRAKE_FACTOR * ctrled.brake);
 
-      let new_angle = if input.left {
-        angle - 0.05
-      } else if input.right {
-        angle + 0.05
-      } else {
-        angle
-      };
+      let new_angle = angle + TURN_RATE * input.turn();
 
       body.set_position(Isometry2::new(Vector2::new(pos.x, pos.y), new_angle));
       body.set_linear_velocity(Vector2::new(new_angle.cos() * v, new_angle.sin() * v));
 
       if input.attack {
-        create_death_explosion(&entities, &lazy, &mut physics, pos.x, pos.y);
+        effect_events.single_write(EffectEvent {
+          effect: Effect::Spawn(Prefab::DeathExplosion),
+          position: PositionComponent {
+            x: pos.x,
+            y: pos.y,
+            angle: 0.0,
+          },
+        });
       }
     });
   }
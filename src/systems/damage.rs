@@ -0,0 +1,111 @@
+use specs::world::*;
+use specs::*;
+
+use crate::components::*;
+use crate::resources::*;
+
+use std::time::Duration;
+
+const FLASH_DURATION_MS: u64 = 100;
+const WOBBLE_DURATION_MS: u64 = 300;
+
+#[derive(Default)]
+pub struct DamageSystem {
+  reader: Option<ReaderId<DamageEvent>>,
+}
+
+impl<'a> System<'a> for DamageSystem {
+  type SystemData = (
+    Read<'a, EntitiesRes>,
+    Read<'a, DamageEvents>,
+    Read<'a, PhysicsSim>,
+    Write<'a, GameEvents>,
+    ReadStorage<'a, ControllableComponent>,
+    ReadStorage<'a, GodModeComponent>,
+    ReadStorage<'a, PositionComponent>,
+    ReadStorage<'a, MeshComponent>,
+    ReadStorage<'a, RigidBodyComponent>,
+    WriteStorage<'a, HealthComponent>,
+    WriteStorage<'a, HitFlashComponent>,
+    WriteStorage<'a, GarbageComponent>,
+  );
+
+  fn run(
+    &mut self,
+    (
+      entities,
+      damage_events,
+      physics,
+      mut game_events,
+      controllable,
+      god_mode,
+      position,
+      mesh,
+      rigid_body,
+      mut health,
+      mut hit_flash,
+      mut garbage,
+    ): Self::SystemData,
+  ) {
+    for event in damage_events.read(&mut self.reader.as_mut().unwrap()) {
+      if !entities.is_alive(event.entity) || god_mode.contains(event.entity) {
+        continue;
+      }
+
+      let health = match health.get_mut(event.entity) {
+        Some(health) => health,
+        None => continue,
+      };
+
+      let was_alive = health.hp > 0.0;
+      health.hp -= event.amount;
+      if health.hp <= 0.0 {
+        garbage.get_mut(event.entity).map(|g| g.is_alive = false);
+      }
+
+      // Anything killed that isn't a player counts as a kill
+      let killed = was_alive && health.hp <= 0.0 && !controllable.contains(event.entity);
+      let kind = rigid_body
+        .get(event.entity)
+        .and_then(|rb| physics.collider_type(rb.handle));
+      if let (true, Some(kind), Some(position)) = (killed, kind, position.get(event.entity)) {
+        game_events.single_write(GameEvent::Killed {
+          kind,
+          x: position.x,
+          y: position.y,
+        });
+      }
+
+      if let (Some(_), Some(position)) =
+        (controllable.get(event.entity), position.get(event.entity))
+      {
+        game_events.single_write(GameEvent::PlayerHit {
+          x: position.x,
+          y: position.y,
+        });
+      }
+
+      // Restart the flash, keeping the colors from before the first hit
+      let flash = match (hit_flash.get(event.entity), mesh.get(event.entity)) {
+        (Some(flash), _) => HitFlashComponent {
+          elapsed: Duration::from_millis(0),
+          ..flash.clone()
+        },
+        (None, Some(mesh)) => HitFlashComponent {
+          flash: Duration::from_millis(FLASH_DURATION_MS),
+          wobble: Duration::from_millis(WOBBLE_DURATION_MS),
+          elapsed: Duration::from_millis(0),
+          original_color: mesh.draw_param.color,
+          original_scale: mesh.draw_param.scale,
+        },
+        (None, None) => continue,
+      };
+      hit_flash.insert(event.entity, flash).unwrap();
+    }
+  }
+
+  fn setup(&mut self, res: &mut Resources) {
+    Self::SystemData::setup(res);
+    self.reader = Some(res.fetch_mut::<DamageEvents>().register_reader());
+  }
+}
@@ -0,0 +1,341 @@
+use ggez::graphics;
+use ggez::timer;
+use ggez::Context;
+use nalgebra::Point2;
+use ncollide2d::shape::{Ball, Cuboid};
+use ncollide2d::world::CollisionGroups;
+use specs::world::*;
+use specs::*;
+
+use crate::components::*;
+use crate::resources::*;
+use crate::utils::*;
+
+// How far ahead a velocity vector reaches, in seconds of travel
+const VELOCITY_SCALE: f32 = 0.1;
+const TEXT_LINE_HEIGHT: f32 = 14.0;
+
+// Collider outlines and velocity vectors, drawn in world space on top of
+// everything else while the overlay is on.
+pub struct DebugShapesSystem<'a> {
+  pub ctx: &'a mut Context,
+}
+
+impl<'a> System<'a> for DebugShapesSystem<'a> {
+  type SystemData = (
+    Read<'a, DebugOverlay>,
+    Read<'a, PhysicsSim>,
+    ReadStorage<'a, RigidBodyComponent>,
+  );
+
+  fn run(&mut self, (overlay, physics, rigid_body): Self::SystemData) {
+    if !overlay.enabled {
+      return;
+    }
+    let ctx = &mut *self.ctx;
+
+    for collider in physics.world.collider_world().colliders() {
+      let color = match collision_group_color(collider.collision_groups()) {
+        Some(color) => color,
+        // Parked pooled bodies collide with nothing, so aren't drawn
+        None => continue,
+      };
+      graphics::set_color(ctx, color).unwrap();
+
+      let position = collider.position();
+      let shape = collider.shape();
+
+      if let Some(ball) = shape.as_shape::<Ball<f32>>() {
+        let center = position.translation.vector;
+        graphics::circle(
+          ctx,
+          graphics::DrawMode::Line(1.0),
+          graphics::Point2::new(center.x, center.y),
+          ball.radius(),
+          0.5,
+        )
+        .unwrap();
+      } else if let Some(cuboid) = shape.as_shape::<Cuboid<f32>>() {
+        let half = cuboid.half_extents();
+        let corners: Vec<graphics::Point2> = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+          .iter()
+          .map(|(sx, sy)| position * Point2::new(sx * half.x, sy * half.y))
+          .map(|p| graphics::Point2::new(p.x, p.y))
+          .collect();
+        graphics::polygon(ctx, graphics::DrawMode::Line(1.0), &corners).unwrap();
+      } else {
+        // Anything else gets its bounding box
+        let aabb = shape.aabb(position);
+        let rect = graphics::Rect::new(
+          aabb.mins().x,
+          aabb.mins().y,
+          aabb.maxs().x - aabb.mins().x,
+          aabb.maxs().y - aabb.mins().y,
+        );
+        graphics::rectangle(ctx, graphics::DrawMode::Line(1.0), rect).unwrap();
+      }
+    }
+
+    graphics::set_color(ctx, graphics::WHITE).unwrap();
+
+    for rb in (&rigid_body).join() {
+      if let Some(body) = physics.world.rigid_body(rb.handle) {
+        let from = body.position().translation.vector;
+        let to = from + body.velocity().linear * VELOCITY_SCALE;
+        graphics::line(
+          ctx,
+          &[
+            graphics::Point2::new(from.x, from.y),
+            graphics::Point2::new(to.x, to.y),
+          ],
+          1.0,
+        )
+        .unwrap();
+      }
+    }
+  }
+}
+
+// Live counters, drawn in screen space in the top left corner
+pub struct DebugStatsSystem<'a> {
+  pub ctx: &'a mut Context,
+  pub font: &'a graphics::Font,
+}
+
+impl<'a> System<'a> for DebugStatsSystem<'a> {
+  type SystemData = (
+    Read<'a, EntitiesRes>,
+    Read<'a, DebugOverlay>,
+    Read<'a, PhysicsSim>,
+    Read<'a, Particles>,
+    ReadStorage<'a, MeshComponent>,
+  );
+
+  fn run(&mut self, (entities, overlay, physics, particles, mesh): Self::SystemData) {
+    if !overlay.enabled {
+      return;
+    }
+    let ctx = &mut *self.ctx;
+
+    let mut lines = vec![
+      format!("FPS: {:.1}", timer::get_fps(ctx)),
+      format!("Entities: {}", (&entities).join().count()),
+      format!("Bodies: {}", physics.world.bodies().count()),
+      format!(
+        "Colliders: {}",
+        physics.world.collider_world().colliders().count()
+      ),
+      // Should track the body count; if it only grows, bodies are leaking
+      format!("PhysicsSim.bodies: {}", physics.bodies.len()),
+      format!("Particles: {}", particles.0.len()),
+    ];
+
+    let mut counts: Vec<(&'static str, usize)> = vec![];
+    for mesh in (&mesh).join() {
+      let kind = mesh_kind(&mesh.key);
+      match counts.iter_mut().find(|(k, _)| *k == kind) {
+        Some((_, count)) => *count += 1,
+        None => counts.push((kind, 1)),
+      }
+    }
+    counts.sort();
+    lines.extend(
+      counts
+        .iter()
+        .map(|(kind, count)| format!("  {}: {}", kind, count)),
+    );
+
+    graphics::set_color(ctx, graphics::WHITE).unwrap();
+    for (i, line) in lines.iter().enumerate() {
+      let text = graphics::Text::new(ctx, line, self.font).unwrap();
+      let dest = graphics::Point2::new(4.0, 4.0 + i as f32 * TEXT_LINE_HEIGHT);
+      graphics::draw(ctx, &text, dest, 0.0).unwrap();
+    }
+  }
+}
+
+// How close the cursor has to be to an entity to inspect it
+const INSPECT_RADIUS: f32 = 32.0;
+
+// Component values of the entity nearest the cursor, drawn in screen space
+// in the bottom left corner while frame stepping
+pub struct DebugInspectorSystem<'a> {
+  pub ctx: &'a mut Context,
+  pub font: &'a graphics::Font,
+  pub height: f32,
+}
+
+impl<'a> System<'a> for DebugInspectorSystem<'a> {
+  type SystemData = (
+    Read<'a, EntitiesRes>,
+    Read<'a, DebugOverlay>,
+    Read<'a, PhysicsSim>,
+    ReadStorage<'a, PositionComponent>,
+    ReadStorage<'a, MeshComponent>,
+    ReadStorage<'a, RigidBodyComponent>,
+    ReadStorage<'a, HealthComponent>,
+    ReadStorage<'a, ShootingComponent>,
+    ReadStorage<'a, GarbageComponent>,
+    ReadStorage<'a, BoundsComponent>,
+    ReadStorage<'a, LifetimeComponent>,
+    ReadStorage<'a, TweenComponent>,
+    ReadStorage<'a, HitFlashComponent>,
+    ReadStorage<'a, TrailComponent>,
+    ReadStorage<'a, PooledComponent>,
+    ReadStorage<'a, GodModeComponent>,
+    ReadStorage<'a, ScriptComponent>,
+  );
+
+  fn run(
+    &mut self,
+    (
+      entities,
+      overlay,
+      physics,
+      position,
+      mesh,
+      rigid_body,
+      health,
+      shooting,
+      garbage,
+      bounds,
+      lifetime,
+      tween,
+      hit_flash,
+      trail,
+      pooled,
+      god_mode,
+      script,
+    ): Self::SystemData,
+  ) {
+    let (cursor_x, cursor_y) = match (overlay.stepping, overlay.cursor) {
+      (true, Some(cursor)) => cursor,
+      _ => return,
+    };
+
+    let distance = |p: &PositionComponent| (p.x - cursor_x).hypot(p.y - cursor_y);
+    let nearest = (&entities, &position)
+      .join()
+      .filter(|(_, p)| distance(p) <= INSPECT_RADIUS)
+      .min_by(|(_, a), (_, b)| distance(a).partial_cmp(&distance(b)).unwrap());
+
+    let (entity, position) = match nearest {
+      Some(nearest) => nearest,
+      None => return,
+    };
+
+    let mut lines = vec![
+      format!("Entity {}", entity.id()),
+      format!(
+        "  position: {:.2}, {:.2} @ {:.3}",
+        position.x, position.y, position.angle
+      ),
+    ];
+
+    if let Some(mesh) = mesh.get(entity) {
+      lines.push(format!(
+        "  mesh: {:?} scale {:.2}, {:.2} color {:?}",
+        mesh.key, mesh.draw_param.scale.x, mesh.draw_param.scale.y, mesh.draw_param.color
+      ));
+    }
+    if let Some(body) = rigid_body
+      .get(entity)
+      .and_then(|rb| physics.world.rigid_body(rb.handle))
+    {
+      let velocity = body.velocity();
+      lines.push(format!(
+        "  body: {:?} velocity {:.2}, {:.2} spin {:.2}",
+        body.status(),
+        velocity.linear.x,
+        velocity.linear.y,
+        velocity.angular
+      ));
+    }
+    if let Some(health) = health.get(entity) {
+      lines.push(format!("  {:?}", health));
+    }
+    if let Some(shooting) = shooting.get(entity) {
+      lines.push(format!(
+        "  shooting: every {:?}, {:?} of game time since the last shot",
+        shooting.every, shooting.since_last_shot
+      ));
+    }
+    if let Some(garbage) = garbage.get(entity) {
+      lines.push(format!("  {:?}", garbage));
+    }
+    if let Some(bounds) = bounds.get(entity) {
+      lines.push(format!("  {:?}", bounds));
+    }
+    if let Some(lifetime) = lifetime.get(entity) {
+      lines.push(format!("  {:?}", lifetime));
+    }
+    if let Some(tween) = tween.get(entity) {
+      let duration = match tween.tween.duration() {
+        Some(duration) => format!("{:.3}s", duration),
+        None => "forever".to_owned(),
+      };
+      lines.push(format!(
+        "  tween: elapsed {:?} of {}, completed {}, on_complete {:?}",
+        tween.elapsed, duration, tween.completed, tween.on_complete
+      ));
+    }
+    if let Some(hit_flash) = hit_flash.get(entity) {
+      lines.push(format!(
+        "  hit_flash: elapsed {:?}, flash {:?}, wobble {:?}",
+        hit_flash.elapsed, hit_flash.flash, hit_flash.wobble
+      ));
+    }
+    if let Some(trail) = trail.get(entity) {
+      lines.push(format!(
+        "  trail: every {:?}, elapsed {:?}",
+        trail.frequency, trail.elapsed
+      ));
+    }
+    if let Some(pooled) = pooled.get(entity) {
+      lines.push(format!("  {:?}", pooled));
+    }
+    if god_mode.contains(entity) {
+      lines.push("  god mode".to_owned());
+    }
+    if let Some(script) = script.get(entity) {
+      lines.push(format!("  script: {}", script.script));
+    }
+
+    let ctx = &mut *self.ctx;
+    let top = self.height - 4.0 - lines.len() as f32 * TEXT_LINE_HEIGHT;
+
+    graphics::set_color(ctx, graphics::WHITE).unwrap();
+    for (i, line) in lines.iter().enumerate() {
+      let text = graphics::Text::new(ctx, line, self.font).unwrap();
+      let dest = graphics::Point2::new(4.0, top + i as f32 * TEXT_LINE_HEIGHT);
+      graphics::draw(ctx, &text, dest, 0.0).unwrap();
+    }
+  }
+}
+
+fn collision_group_color(groups: &CollisionGroups) -> Option<graphics::Color> {
+  if groups.is_member_of(PLAYER_BODY_COLLISION_GROUP) {
+    Some(boost_color())
+  } else if groups.is_member_of(PLAYER_PROJECTILE_COLLISION_GROUP) {
+    Some(trail_color())
+  } else if groups.is_member_of(AMMO_BODY_COLLISION_GROUP) {
+    Some(ammo_color())
+  } else if groups.is_member_of(ENEMY_BODY_COLLISION_GROUP) {
+    Some(enemy_color())
+  } else {
+    None
+  }
+}
+
+fn mesh_kind(key: &MeshKey) -> &'static str {
+  match key {
+    MeshKey::Player => "Player",
+    MeshKey::Projectile => "Projectile",
+    MeshKey::Ammo => "Ammo",
+    MeshKey::OutOfBoundsExplosion => "OutOfBoundsExplosion",
+    MeshKey::ExplosionLine { .. } => "ExplosionLine",
+    MeshKey::Particle => "Particle",
+    MeshKey::TrailParticle { .. } => "TrailParticle",
+    MeshKey::Enemy { .. } => "Enemy",
+  }
+}
@@ -0,0 +1,82 @@
+use specs::world::*;
+use specs::*;
+
+use crate::components::*;
+use crate::entities::*;
+use crate::resources::*;
+
+#[derive(Default)]
+pub struct EffectSystem {
+  reader: Option<ReaderId<EffectEvent>>,
+}
+
+impl<'a> System<'a> for EffectSystem {
+  type SystemData = (
+    Read<'a, EntitiesRes>,
+    Read<'a, LazyUpdate>,
+    Read<'a, GameSettings>,
+    Read<'a, EffectEvents>,
+    Write<'a, PhysicsSim>,
+    Write<'a, Pools>,
+    Write<'a, GameRng>,
+    Write<'a, GameEvents>,
+    Write<'a, Score>,
+  );
+
+  fn run(
+    &mut self,
+    (
+      entities,
+      lazy,
+      game_settings,
+      effect_events,
+      mut physics,
+      mut pools,
+      mut rng,
+      mut game_events,
+      mut score,
+    ): Self::SystemData,
+  ) {
+    for event in effect_events.read(&mut self.reader.as_mut().unwrap()) {
+      let x = event.position.x;
+      let y = event.position.y;
+
+      match &event.effect {
+        Effect::Spawn(prefab) => {
+          spawn_prefab(
+            *prefab,
+            &entities,
+            &lazy,
+            &game_settings,
+            &mut physics,
+            &mut pools,
+            &mut rng,
+            event.position.clone(),
+          );
+
+          match prefab {
+            Prefab::DeathExplosion => game_events.single_write(GameEvent::DeathExplosion { x, y }),
+            Prefab::OutOfBoundsExplosion => {
+              game_events.single_write(GameEvent::OutOfBoundsExplosion { x, y })
+            }
+            _ => (),
+          }
+        }
+        Effect::Sound(name) => game_events.single_write(GameEvent::PlaySound {
+          name: name.clone(),
+          x,
+          y,
+        }),
+        Effect::AddScore(points) => score.0 += points,
+        Effect::Event(game_event) => game_events.single_write(game_event.clone()),
+        // Scripts are run by ScriptSystem
+        Effect::SpawnEnemy(_) | Effect::Script { .. } => (),
+      }
+    }
+  }
+
+  fn setup(&mut self, res: &mut Resources) {
+    Self::SystemData::setup(res);
+    self.reader = Some(res.fetch_mut::<EffectEvents>().register_reader());
+  }
+}
@@ -10,21 +10,84 @@ pub struct GarbageSystem;
 impl<'a> System<'a> for GarbageSystem {
   type SystemData = (
     Read<'a, EntitiesRes>,
+    Read<'a, LazyUpdate>,
     Write<'a, PhysicsSim>,
+    Write<'a, Pools>,
+    Write<'a, EffectEvents>,
     ReadStorage<'a, GarbageComponent>,
     ReadStorage<'a, RigidBodyComponent>,
+    ReadStorage<'a, OnDeathComponent>,
+    ReadStorage<'a, PositionComponent>,
+    ReadStorage<'a, PooledComponent>,
   );
 
-  fn run(&mut self, (entities, mut physics, garbage, rigid_body): Self::SystemData) {
+  fn run(
+    &mut self,
+    (
+      entities,
+      lazy,
+      mut physics,
+      mut pools,
+      mut effect_events,
+      garbage,
+      rigid_body,
+      on_death,
+      position,
+      pooled,
+    ): Self::SystemData,
+  ) {
+    pools.recycle();
+
     for (entity, garbage) in (&entities, &garbage).join() {
       if !garbage.is_alive {
+        // Queue death effects before deleting, while the components are
+        // still readable
+        if !garbage.culled {
+          if let (Some(on_death), Some(position)) = (on_death.get(entity), position.get(entity)) {
+            for effect in on_death.effects.iter() {
+              effect_events.single_write(EffectEvent {
+                effect: effect.clone(),
+                position: position.clone(),
+              });
+            }
+          }
+        }
+
+        let body = rigid_body.get(entity).map(|body| body.handle);
+
+        // Pooled entities keep their entity and body, parked until they're
+        // re-armed by their factory
+        if let Some(pooled) = pooled.get(entity) {
+          if let Some(body) = body {
+            physics.disable_body(body);
+          }
+          pools.release(pooled.kind, PooledEntity { entity, body });
+          deactivate(entity, &lazy);
+          continue;
+        }
+
         entities.delete(entity).unwrap();
-        let rigid_body_component: Option<&RigidBodyComponent> = rigid_body.get(entity);
-        if let Some(body) = rigid_body_component {
-          physics.world.remove_bodies(&[body.handle]);
-          physics.bodies.remove(&body.handle);
+        if let Some(body) = body {
+          physics.remove_body(body);
         }
       }
     }
   }
 }
+
+// Strips everything a pooled entity might have been spawned with, so no
+// system picks it up while it waits in its pool
+fn deactivate(entity: Entity, lazy: &LazyUpdate) {
+  lazy.remove::<PositionComponent>(entity);
+  lazy.remove::<MeshComponent>(entity);
+  lazy.remove::<RigidBodyComponent>(entity);
+  lazy.remove::<TweenComponent>(entity);
+  lazy.remove::<LifetimeComponent>(entity);
+  lazy.remove::<GarbageComponent>(entity);
+  lazy.remove::<BoundsComponent>(entity);
+  lazy.remove::<ExplodeBoundsComponent>(entity);
+  lazy.remove::<OnSpawnComponent>(entity);
+  lazy.remove::<OnDeathComponent>(entity);
+  lazy.remove::<HitFlashComponent>(entity);
+  lazy.remove::<OwnerComponent>(entity);
+}
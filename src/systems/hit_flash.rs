@@ -0,0 +1,56 @@
+use ggez::graphics;
+use specs::world::*;
+use specs::*;
+
+use crate::components::*;
+use crate::resources::*;
+use crate::utils::*;
+
+// How far the scale swings at the start of a wobble, and how fast
+const WOBBLE_AMPLITUDE: f32 = 0.25;
+const WOBBLE_FREQUENCY: f32 = 30.0;
+
+pub struct HitFlashSystem;
+
+impl<'a> System<'a> for HitFlashSystem {
+  type SystemData = (
+    Read<'a, EntitiesRes>,
+    Read<'a, UpdateTime>,
+    WriteStorage<'a, HitFlashComponent>,
+    WriteStorage<'a, MeshComponent>,
+  );
+
+  fn run(&mut self, (entities, update_time, mut hit_flash, mut mesh): Self::SystemData) {
+    let mut finished = vec![];
+
+    for (entity, flash, mesh) in (&entities, &mut hit_flash, &mut mesh).join() {
+      flash.elapsed += update_time.0;
+
+      mesh.draw_param.color = if flash.elapsed < flash.flash {
+        Some(graphics::WHITE)
+      } else {
+        flash.original_color
+      };
+
+      mesh.draw_param.scale = if flash.elapsed < flash.wobble {
+        let t = seconds(flash.elapsed);
+        let decay = 1.0 - t / seconds(flash.wobble);
+        let wobble = 1.0 + WOBBLE_AMPLITUDE * decay * (t * WOBBLE_FREQUENCY).sin();
+        graphics::Point2::new(
+          flash.original_scale.x * wobble,
+          flash.original_scale.y * wobble,
+        )
+      } else {
+        flash.original_scale
+      };
+
+      if flash.elapsed >= flash.flash && flash.elapsed >= flash.wobble {
+        finished.push(entity);
+      }
+    }
+
+    for entity in finished {
+      hit_flash.remove(entity);
+    }
+  }
+}
@@ -0,0 +1,89 @@
+use ggez::graphics;
+use ggez::Context;
+use specs::*;
+
+use crate::components::*;
+use crate::resources::*;
+use crate::utils::*;
+
+const HP_BAR_WIDTH: f32 = 160.0;
+const HP_BAR_HEIGHT: f32 = 8.0;
+const MARGIN: f32 = 8.0;
+
+// A panel per local player along the bottom of the screen, player one on
+// the left and player two on the right
+pub struct HudSystem<'a> {
+  pub ctx: &'a mut Context,
+  pub font: &'a graphics::Font,
+  pub width: f32,
+  pub height: f32,
+}
+
+impl<'a> System<'a> for HudSystem<'a> {
+  type SystemData = (
+    Read<'a, GameSettings>,
+    Read<'a, RunStats>,
+    ReadStorage<'a, ControllableComponent>,
+    ReadStorage<'a, HealthComponent>,
+  );
+
+  fn run(&mut self, (game_settings, stats, controllable, health): Self::SystemData) {
+    let ctx = &mut *self.ctx;
+
+    for player in 0..game_settings.players {
+      // A destroyed ship has no entity left to read
+      let hp = (&controllable, &health)
+        .join()
+        .find(|(controllable, _)| controllable.player == player)
+        .map(|(_, health)| (health.hp.max(0.0), health.max_hp));
+      let player_stats = stats.players.get(player).cloned().unwrap_or_default();
+
+      let label = match hp {
+        Some((hp, max_hp)) => format!(
+          "P{}  HP {:.0}/{:.0}  shots {}  pickups {}",
+          player + 1,
+          hp,
+          max_hp,
+          player_stats.shots_fired,
+          player_stats.pickups
+        ),
+        None => format!("P{}  down", player + 1),
+      };
+
+      let text = graphics::Text::new(ctx, &label, self.font).unwrap();
+      let x = if player % 2 == 0 {
+        MARGIN
+      } else {
+        self.width - MARGIN - HP_BAR_WIDTH.max(text.width() as f32)
+      };
+      let bar_y = self.height - MARGIN - HP_BAR_HEIGHT;
+      let text_y = bar_y - 4.0 - text.height() as f32;
+
+      graphics::set_color(ctx, player_color(player)).unwrap();
+      graphics::draw(ctx, &text, graphics::Point2::new(x, text_y), 0.0).unwrap();
+      graphics::rectangle(
+        ctx,
+        graphics::DrawMode::Line(1.0),
+        graphics::Rect::new(x, bar_y, HP_BAR_WIDTH, HP_BAR_HEIGHT),
+      )
+      .unwrap();
+
+      if let Some((hp, max_hp)) = hp {
+        let fill = if max_hp > 0.0 {
+          (hp / max_hp).min(1.0)
+        } else {
+          0.0
+        };
+        graphics::set_color(ctx, hp_color()).unwrap();
+        graphics::rectangle(
+          ctx,
+          graphics::DrawMode::Fill,
+          graphics::Rect::new(x, bar_y, HP_BAR_WIDTH * fill, HP_BAR_HEIGHT),
+        )
+        .unwrap();
+      }
+    }
+
+    graphics::set_color(ctx, graphics::WHITE).unwrap();
+  }
+}
@@ -0,0 +1,34 @@
+use specs::world::*;
+use specs::*;
+
+use crate::components::*;
+use crate::resources::*;
+
+pub struct LifecycleSystem;
+
+impl<'a> System<'a> for LifecycleSystem {
+  type SystemData = (
+    Read<'a, EntitiesRes>,
+    Write<'a, EffectEvents>,
+    ReadStorage<'a, PositionComponent>,
+    WriteStorage<'a, OnSpawnComponent>,
+  );
+
+  fn run(&mut self, (entities, mut effect_events, position, mut on_spawn): Self::SystemData) {
+    let mut spawned = vec![];
+
+    for (entity, on_spawn, position) in (&entities, &on_spawn, &position).join() {
+      for effect in on_spawn.effects.iter() {
+        effect_events.single_write(EffectEvent {
+          effect: effect.clone(),
+          position: position.clone(),
+        });
+      }
+      spawned.push(entity);
+    }
+
+    for entity in spawned {
+      on_spawn.remove(entity);
+    }
+  }
+}
@@ -1,23 +1,51 @@
+pub mod audio;
 pub mod bounds;
+pub mod camera;
 pub mod collision;
 pub mod controllable;
+pub mod damage;
+pub mod debug;
+pub mod effect;
 pub mod garbage;
+pub mod hit_flash;
+pub mod hud;
+pub mod lifecycle;
 pub mod lifetime;
+pub mod particle;
 pub mod physics;
 pub mod position;
+pub mod profiler;
 pub mod rendering;
+pub mod script;
 pub mod shooting;
+pub mod spatial;
 pub mod spawn;
+pub mod stats;
+pub mod trail;
 pub mod tween;
 
+pub use audio::*;
 pub use bounds::*;
+pub use camera::*;
 pub use collision::*;
 pub use controllable::*;
+pub use damage::*;
+pub use debug::*;
+pub use effect::*;
 pub use garbage::*;
+pub use hit_flash::*;
+pub use hud::*;
+pub use lifecycle::*;
 pub use lifetime::*;
+pub use particle::*;
 pub use physics::*;
 pub use position::*;
+pub use profiler::*;
 pub use rendering::*;
+pub use script::*;
 pub use shooting::*;
+pub use spatial::*;
 pub use spawn::*;
+pub use stats::*;
+pub use trail::*;
 pub use tween::*;
@@ -0,0 +1,89 @@
+use rand::Rng;
+use specs::world::*;
+use specs::*;
+use std::time::Duration;
+
+use crate::components::*;
+use crate::resources::*;
+
+pub struct ParticleSystem;
+
+impl<'a> System<'a> for ParticleSystem {
+  type SystemData = (
+    Read<'a, UpdateTime>,
+    Write<'a, Particles>,
+    ReadStorage<'a, PositionComponent>,
+    WriteStorage<'a, ParticleEmitterComponent>,
+  );
+
+  fn run(&mut self, (update_time, mut particles, position, mut emitters): Self::SystemData) {
+    let dt = update_time.0;
+    let dt_seconds = dt.subsec_nanos() as f32 / 1_000_000_000.0;
+
+    for particle in particles.0.iter_mut() {
+      particle.age += dt;
+      particle.x += particle.vx * dt_seconds;
+      particle.y += particle.vy * dt_seconds;
+    }
+    particles
+      .0
+      .retain(|particle| particle.age < particle.lifetime);
+
+    // Particles are cosmetic, so they use thread_rng and leave GameRng alone
+    let mut rng = rand::thread_rng();
+
+    for (position, emitter) in (&position, &mut emitters).join() {
+      emitter.elapsed += dt;
+
+      // A zero frequency emits once per tick
+      let emissions = if emitter.frequency > Duration::from_millis(0) {
+        let mut emissions = 0;
+        while emitter.elapsed >= emitter.frequency {
+          emitter.elapsed -= emitter.frequency;
+          emissions += 1;
+        }
+        emissions
+      } else {
+        emitter.elapsed = Duration::from_millis(0);
+        1
+      };
+
+      for _ in 0..emissions {
+        let (cos, sin) = (position.angle.cos(), position.angle.sin());
+        let x = position.x + emitter.offset.0 * cos - emitter.offset.1 * sin;
+        let y = position.y + emitter.offset.0 * sin + emitter.offset.1 * cos;
+
+        for _ in 0..emitter.burst {
+          if particles.0.len() >= MAX_PARTICLES {
+            break;
+          }
+
+          let angle =
+            position.angle + emitter.angle + range(&mut rng, -emitter.spread, emitter.spread);
+          let speed = range(&mut rng, emitter.speed.0, emitter.speed.1);
+
+          particles.0.push(Particle {
+            x,
+            y,
+            angle,
+            vx: angle.cos() * speed,
+            vy: angle.sin() * speed,
+            age: Duration::from_millis(0),
+            lifetime: emitter.lifetime,
+            size: emitter.size.clone(),
+            color: emitter.color.clone(),
+          });
+        }
+      }
+    }
+  }
+}
+
+// gen_range panics on an empty range, and a spread of zero is common
+fn range<R: Rng>(rng: &mut R, low: f32, high: f32) -> f32 {
+  if high > low {
+    rng.gen_range(low, high)
+  } else {
+    low
+  }
+}
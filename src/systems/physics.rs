@@ -1,84 +1,230 @@
 use crate::components::*;
 use crate::resources::*;
+use nalgebra::{Point2, Vector2};
 use ncollide2d::events::ContactEvent;
-use nphysics2d::object::{Collider, RigidBody};
+use ncollide2d::query::Proximity;
+use nphysics2d::object::{BodyHandle, Collider, ColliderHandle, RigidBody};
 use nphysics2d::world::ColliderWorld;
 use shrev::EventChannel;
 use specs::world::*;
 use specs::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
 
+#[derive(Default)]
 pub struct PhysicsSystem;
 
 impl<'a> System<'a> for PhysicsSystem {
   type SystemData = (
     Read<'a, UpdateTime>,
+    Read<'a, CollisionRegistry>,
     Write<'a, PhysicsSim>,
     Write<'a, CollisionEvents>,
+    Write<'a, ProximityEvents>,
   );
 
-  fn run(&mut self, (update_time, mut physics, mut collision_events): Self::SystemData) {
+  fn run(
+    &mut self,
+    (
+      update_time,
+      registry,
+      mut physics,
+      mut collision_events,
+      mut proximity_events,
+    ): Self::SystemData,
+  ) {
     // Step physics world
     let dt_seconds = update_time.0.subsec_nanos() as f32 / 1_000_000_000.0;
     physics.world.set_timestep(dt_seconds);
     physics.world.step();
 
-    // Resolve collisions and create collision events
-    let collider_world: &ColliderWorld<f32> = physics.world.collider_world();
-
-    let contact_events = collider_world.contact_events();
-
-    let colliders: Vec<(&Collider<f32>, &Collider<f32>)> = contact_events
-      .iter()
-      .flat_map(|contact_event| match contact_event {
-        ContactEvent::Started(handle1, handle2) => collider_world
-          .contact_pair(*handle1, *handle2, true)
-          .map(|(collider1, collider2, _, _)| (collider1, collider2)),
-        ContactEvent::Stopped(_, _) => None,
-      })
-      .collect();
-
-    for (collider1, collider2) in colliders {
-      let collider1_body_handle = collider1.body();
-      let collider2_body_handle = collider2.body();
-
-      let collider1_entity = physics.bodies.get(&collider1_body_handle);
-      let collider2_entity = physics.bodies.get(&collider2_body_handle);
-
-      let joined = (
-        collider1_entity,
-        collider2_entity,
-        physics.world.rigid_body(collider2_body_handle),
-      );
-
-      match joined {
-        (Some(e1), Some(e2), Some(body)) => {
-          let rb_position = body.position().translation;
-
-          let collider_type_1 = collider1.name().parse::<ColliderType>().unwrap();
-          let collider_type_2 = collider2.name().parse::<ColliderType>().unwrap();
-
-          let collision_type = match (collider_type_1, collider_type_2) {
-            (ColliderType::Player, ColliderType::Ammo) => Some(CollisionType::PlayerAmmo {
-              player: *e1,
-              ammo: *e2,
-            }),
-            (ColliderType::Ammo, ColliderType::Player) => Some(CollisionType::PlayerAmmo {
-              player: *e2,
-              ammo: *e1,
-            }),
-            (_, _) => None,
-          };
-
-          if let Some(ct) = collision_type {
-            collision_events.single_write(CollisionEvent {
-              collision_type: ct,
-              x: rb_position.x,
-              y: rb_position.y,
-            })
-          }
+    let mut contacts = mem::replace(&mut physics.contacts, Default::default());
+    emit_events(
+      &physics,
+      &registry,
+      &mut contacts,
+      &mut collision_events,
+      &mut proximity_events,
+    );
+    physics.contacts = contacts;
+  }
+}
+
+// Resolves this step's contacts and proximities into events
+fn emit_events(
+  physics: &PhysicsSim,
+  registry: &CollisionRegistry,
+  contacts: &mut BTreeMap<(usize, usize), ActiveContact>,
+  collision_events: &mut CollisionEvents,
+  proximity_events: &mut ProximityEvents,
+) {
+  let collider_world: &ColliderWorld<f32> = physics.world.collider_world();
+
+  let participant = |handle: ColliderHandle| -> Option<(ColliderType, Entity, BodyHandle)> {
+    let info = physics.colliders.get(&handle)?;
+    let entity = physics.bodies.get(&info.body)?;
+    Some((info.collider_type, *entity, info.body))
+  };
+
+  // The collision a pair of colliders represents, whether the registry
+  // swapped them, and the second participant's body
+  let resolve = |handle1: ColliderHandle,
+                 handle2: ColliderHandle|
+   -> Option<(CollisionType, bool, BodyHandle)> {
+    let collider1 = participant(handle1)?;
+    let collider2 = participant(handle2)?;
+    let (collision_type, swapped) =
+      registry.resolve((collider1.0, collider1.1), (collider2.0, collider2.1))?;
+    let second_body = if swapped { collider1.2 } else { collider2.2 };
+    Some((collision_type, swapped, second_body))
+  };
+
+  let body_position = |body: BodyHandle| -> Option<(f32, f32)> {
+    let translation = physics.world.rigid_body(body)?.position().translation;
+    Some((translation.x, translation.y))
+  };
+
+  // Deepest point of the pair's contact manifold, with the normal
+  // pointing from the first participant to the second
+  let sample = |handle1: ColliderHandle,
+                handle2: ColliderHandle,
+                swapped: bool|
+   -> Option<(Point2<f32>, Vector2<f32>)> {
+    let (collider1, _, _, manifold) = collider_world.contact_pair(handle1, handle2, true)?;
+    let contact = &manifold.deepest_contact()?.contact;
+
+    let point = Point2::new(
+      (contact.world1.x + contact.world2.x) / 2.0,
+      (contact.world1.y + contact.world2.y) / 2.0,
+    );
+    let mut normal: Vector2<f32> = *contact.normal;
+    if (collider1.handle() != handle1) != swapped {
+      normal = -normal;
+    }
+
+    Some((point, normal))
+  };
+
+  let mut started = BTreeSet::new();
+
+  for contact_event in collider_world.contact_events() {
+    match contact_event {
+      ContactEvent::Started(handle1, handle2) => {
+        let (collision_type, swapped, second_body) = match resolve(*handle1, *handle2) {
+          Some(resolved) => resolved,
+          None => continue,
+        };
+        let (x, y) = body_position(second_body).unwrap_or((0.0, 0.0));
+        let (point, normal) =
+          sample(*handle1, *handle2, swapped).unwrap_or((Point2::new(x, y), Vector2::zeros()));
+
+        collision_events.single_write(CollisionEvent {
+          collision_type,
+          phase: ContactPhase::Started,
+          x,
+          y,
+          point,
+          normal,
+        });
+
+        started.insert((handle1.0, handle2.0));
+        contacts.insert(
+          (handle1.0, handle2.0),
+          ActiveContact {
+            colliders: (*handle1, *handle2),
+            collision_type,
+            swapped,
+            second_body,
+            x,
+            y,
+            point,
+            normal,
+          },
+        );
+      }
+      ContactEvent::Stopped(handle1, handle2) => {
+        let contact = contacts
+          .remove(&(handle1.0, handle2.0))
+          .or_else(|| contacts.remove(&(handle2.0, handle1.0)));
+
+        if let Some(contact) = contact {
+          collision_events.single_write(stopped_event(&contact));
         }
-        (_, _, _) => {}
-      };
+      }
     }
   }
+
+  // Everything still in contact from an earlier tick is ongoing. Pairs
+  // whose colliders were removed never get a Stopped contact event, so
+  // they're stopped here instead.
+  let mut removed = vec![];
+
+  for (key, contact) in contacts.iter_mut() {
+    if started.contains(key) {
+      continue;
+    }
+    let (handle1, handle2) = contact.colliders;
+
+    if participant(handle1).is_none() || participant(handle2).is_none() {
+      removed.push(*key);
+      continue;
+    }
+
+    if let Some((x, y)) = body_position(contact.second_body) {
+      contact.x = x;
+      contact.y = y;
+    }
+    if let Some((point, normal)) = sample(handle1, handle2, contact.swapped) {
+      contact.point = point;
+      contact.normal = normal;
+    }
+
+    collision_events.single_write(CollisionEvent {
+      collision_type: contact.collision_type,
+      phase: ContactPhase::Ongoing,
+      x: contact.x,
+      y: contact.y,
+      point: contact.point,
+      normal: contact.normal,
+    });
+  }
+
+  for key in removed {
+    if let Some(contact) = contacts.remove(&key) {
+      collision_events.single_write(stopped_event(&contact));
+    }
+  }
+
+  // Sensors report overlaps rather than contacts
+  for proximity_event in collider_world.proximity_events() {
+    let status = match (proximity_event.prev_status, proximity_event.new_status) {
+      (Proximity::Intersecting, Proximity::Intersecting) => continue,
+      (_, Proximity::Intersecting) => ProximityStatus::Entered,
+      (Proximity::Intersecting, _) => ProximityStatus::Exited,
+      _ => continue,
+    };
+
+    if let Some((collision_type, _, second_body)) =
+      resolve(proximity_event.collider1, proximity_event.collider2)
+    {
+      let (x, y) = body_position(second_body).unwrap_or((0.0, 0.0));
+      proximity_events.single_write(ProximityEvent {
+        collision_type,
+        status,
+        x,
+        y,
+      });
+    }
+  }
+}
+
+fn stopped_event(contact: &ActiveContact) -> CollisionEvent {
+  CollisionEvent {
+    collision_type: contact.collision_type,
+    phase: ContactPhase::Stopped,
+    x: contact.x,
+    y: contact.y,
+    point: contact.point,
+    normal: contact.normal,
+  }
 }
@@ -0,0 +1,96 @@
+use ggez::graphics;
+use ggez::Context;
+use specs::*;
+use std::time::{Duration, Instant};
+
+use crate::resources::*;
+
+const TEXT_LINE_HEIGHT: f32 = 14.0;
+
+// Wraps a system, timing every run into the Profiler
+pub struct Profiled<S> {
+  name: &'static str,
+  system: S,
+}
+
+impl<S> Profiled<S> {
+  pub fn new(name: &'static str, system: S) -> Profiled<S> {
+    Profiled { name, system }
+  }
+}
+
+impl<'a, S: System<'a>> System<'a> for Profiled<S> {
+  type SystemData = (Read<'a, Profiler>, S::SystemData);
+
+  fn run(&mut self, (profiler, data): Self::SystemData) {
+    let start = Instant::now();
+    self.system.run(data);
+    profiler.record(self.name, start, start.elapsed());
+  }
+
+  fn setup(&mut self, res: &mut Resources) {
+    Read::<Profiler>::setup(res);
+    self.system.setup(res);
+  }
+}
+
+pub trait WithProfiled<'a, 'b> {
+  // Same as with(), but the system's runs show up in the profiler
+  fn with_profiled<S>(self, system: S, name: &'static str, dependencies: &[&str]) -> Self
+  where
+    S: for<'c> System<'c> + Send + 'a;
+}
+
+impl<'a, 'b> WithProfiled<'a, 'b> for DispatcherBuilder<'a, 'b> {
+  fn with_profiled<S>(self, system: S, name: &'static str, dependencies: &[&str]) -> Self
+  where
+    S: for<'c> System<'c> + Send + 'a,
+  {
+    self.with(Profiled::new(name, system), name, dependencies)
+  }
+}
+
+// Rolling per-system timings, drawn in screen space while toggled on
+pub struct ProfilerOverlaySystem<'a> {
+  pub ctx: &'a mut Context,
+  pub font: &'a graphics::Font,
+  pub width: f32,
+}
+
+impl<'a> System<'a> for ProfilerOverlaySystem<'a> {
+  type SystemData = (Read<'a, DebugOverlay>, Read<'a, Profiler>);
+
+  fn run(&mut self, (overlay, profiler): Self::SystemData) {
+    if !overlay.profiler {
+      return;
+    }
+    let ctx = &mut *self.ctx;
+
+    let mut lines = vec![format!(
+      "{:<24}{:>8}{:>8}{:>8}{:>8}{:>8}",
+      "system (ms)", "avg", "p50", "p95", "p99", "max"
+    )];
+    lines.extend(profiler.timings().iter().map(|t| {
+      format!(
+        "{:<24}{:>8.3}{:>8.3}{:>8.3}{:>8.3}{:>8.3}",
+        t.name,
+        millis(t.average),
+        millis(t.p50),
+        millis(t.p95),
+        millis(t.p99),
+        millis(t.max)
+      )
+    }));
+
+    graphics::set_color(ctx, graphics::WHITE).unwrap();
+    for (i, line) in lines.iter().enumerate() {
+      let text = graphics::Text::new(ctx, line, self.font).unwrap();
+      let dest = graphics::Point2::new(self.width / 2.0, 4.0 + i as f32 * TEXT_LINE_HEIGHT);
+      graphics::draw(ctx, &text, dest, 0.0).unwrap();
+    }
+  }
+}
+
+fn millis(duration: Duration) -> f64 {
+  duration.as_secs() as f64 * 1_000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+}
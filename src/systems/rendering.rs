@@ -1,25 +1,45 @@
 use ggez::graphics;
 use ggez::Context;
 use specs::*;
+use std::collections::HashMap;
 
 use crate::components::*;
+use crate::entities::*;
 use crate::resources::*;
+use crate::utils::*;
+
+// Built meshes, keyed by the shape they were built from. Building a
+// MeshBuilder tessellates and uploads it, which is far too slow to do for
+// every entity on every frame.
+pub type MeshCache = HashMap<MeshKey, graphics::Mesh>;
 
 pub struct RenderingSystem<'a> {
   pub ctx: &'a mut ggez::Context,
+  pub meshes: &'a mut MeshCache,
 }
 
 impl<'a> System<'a> for RenderingSystem<'a> {
   type SystemData = (
+    Read<'a, Particles>,
     ReadStorage<'a, MeshComponent>,
     ReadStorage<'a, PositionComponent>,
   );
 
-  fn run(&mut self, (mesh, position): Self::SystemData) {
+  fn run(&mut self, (particles, mesh, position): Self::SystemData) {
     for (mesh, position) in (&mesh, &position).join() {
-      let drawable: graphics::Mesh = mesh.mesh.build(self.ctx).unwrap();
-      let dest = ggez::graphics::Point2::new(position.x, position.y);
-      let angle = position.angle;
+      let ctx = &mut *self.ctx;
+      let drawable: &graphics::Mesh = self
+        .meshes
+        .entry(mesh.key)
+        .or_insert_with(|| mesh_for(mesh.key).build(ctx).unwrap());
+      // The mesh's own dest and rotation are offsets from the entity's
+      // position, so tweens can move and spin a mesh without touching
+      // the physics body.
+      let dest = ggez::graphics::Point2::new(
+        position.x + mesh.draw_param.dest.x,
+        position.y + mesh.draw_param.dest.y,
+      );
+      let angle = position.angle + mesh.draw_param.rotation;
 
       let draw_param = graphics::DrawParam {
         dest: dest,
@@ -31,7 +51,29 @@ impl<'a> System<'a> for RenderingSystem<'a> {
         color: mesh.draw_param.color,
       };
 
-      ggez::graphics::draw_ex(self.ctx, &drawable, draw_param).unwrap();
+      ggez::graphics::draw_ex(ctx, drawable, draw_param).unwrap();
+    }
+
+    // Every particle is the same unit square, scaled and tinted per draw
+    let ctx = &mut *self.ctx;
+    let quad: &graphics::Mesh = self
+      .meshes
+      .entry(MeshKey::Particle)
+      .or_insert_with(|| mesh_for(MeshKey::Particle).build(ctx).unwrap());
+
+    for particle in particles.0.iter() {
+      let t = particle.progress();
+      let size = particle.size.at(t);
+
+      let draw_param = graphics::DrawParam {
+        dest: graphics::Point2::new(particle.x, particle.y),
+        rotation: particle.angle,
+        scale: graphics::Point2::new(size, size),
+        color: Some(particle.color.at(t)),
+        ..Default::default()
+      };
+
+      ggez::graphics::draw_ex(ctx, quad, draw_param).unwrap();
     }
   }
 }
@@ -0,0 +1,218 @@
+use nalgebra::{Point2, Vector2};
+use rhai::{Dynamic, FLOAT};
+use specs::world::*;
+use specs::*;
+use std::mem;
+
+use crate::components::*;
+use crate::entities::*;
+use crate::resources::*;
+use crate::scripting::*;
+use crate::utils::*;
+
+// Runs the director's tick function, every scripted entity's update
+// function and any script effects, then carries out whatever the scripts
+// asked for
+pub struct ScriptSystem {
+  host: ScriptHost,
+  reader: Option<ReaderId<EffectEvent>>,
+}
+
+impl ScriptSystem {
+  pub fn new(host: ScriptHost) -> ScriptSystem {
+    ScriptSystem { host, reader: None }
+  }
+}
+
+impl<'a> System<'a> for ScriptSystem {
+  type SystemData = (
+    Read<'a, EntitiesRes>,
+    Read<'a, LazyUpdate>,
+    Read<'a, GameSettings>,
+    Read<'a, UpdateTime>,
+    Read<'a, EffectEvents>,
+    Read<'a, SpatialQuery>,
+    Write<'a, PhysicsSim>,
+    Write<'a, Pools>,
+    Write<'a, GameRng>,
+    Write<'a, GameEvents>,
+    Write<'a, ScriptVars>,
+    ReadStorage<'a, PositionComponent>,
+    ReadStorage<'a, ControllableComponent>,
+    ReadStorage<'a, RigidBodyComponent>,
+    WriteStorage<'a, ScriptComponent>,
+  );
+
+  fn run(
+    &mut self,
+    (
+      entities,
+      lazy,
+      game_settings,
+      update_time,
+      effect_events,
+      spatial,
+      mut physics,
+      mut pools,
+      mut rng,
+      mut game_events,
+      mut director_vars,
+      position,
+      controllable,
+      rigid_body,
+      mut script,
+    ): Self::SystemData,
+  ) {
+    let dt = Dynamic::from(seconds(update_time.0) as FLOAT);
+
+    let nearest_player = |x: f32, y: f32| {
+      spatial
+        .nearest(Point2::new(x, y), 1, &[ColliderType::Player])
+        .first()
+        .and_then(|ship| position.get(*ship))
+        .map(|ship| (ship.x, ship.y))
+    };
+
+    let effects: Vec<(Effect, PositionComponent)> = effect_events
+      .read(&mut self.reader.as_mut().unwrap())
+      .filter(|event| match event.effect {
+        Effect::SpawnEnemy(_) | Effect::Script { .. } => true,
+        _ => false,
+      })
+      .map(|event| (event.effect.clone(), event.position.clone()))
+      .collect();
+
+    {
+      let mut context = self.host.context();
+      context.players = vec![None; game_settings.players];
+      for (controllable, position) in (&controllable, &position).join() {
+        if let Some(ship) = context.players.get_mut(controllable.player) {
+          *ship = Some((position.x, position.y));
+        }
+      }
+      context.world_size = (
+        game_settings.world_width as f32,
+        game_settings.world_height as f32,
+      );
+      context.rng = Some(rng.clone());
+      context.target = None;
+      context.vars = mem::replace(&mut director_vars.0, Default::default());
+    }
+
+    self.host.call(DIRECTOR_SCRIPT, "tick", Some(dt.clone()));
+    director_vars.0 = mem::replace(&mut self.host.context().vars, Default::default());
+
+    for (entity, script, position, rigid_body) in
+      (&entities, &mut script, &position, rigid_body.maybe()).join()
+    {
+      let velocity = rigid_body
+        .and_then(|rigid_body| physics.world.rigid_body(rigid_body.handle))
+        .map_or((0.0, 0.0), |body| {
+          let linear = body.velocity().linear;
+          (linear.x, linear.y)
+        });
+
+      {
+        let mut context = self.host.context();
+        context.target = Some(ScriptTarget {
+          entity: Some(entity),
+          x: position.x,
+          y: position.y,
+          angle: position.angle,
+          velocity,
+          nearest_player: nearest_player(position.x, position.y),
+        });
+        context.vars = mem::replace(&mut script.vars, Default::default());
+      }
+      self.host.call(&script.script, "update", Some(dt.clone()));
+      script.vars = mem::replace(&mut self.host.context().vars, Default::default());
+    }
+
+    for (effect, position) in effects {
+      match effect {
+        Effect::SpawnEnemy(script) => {
+          self
+            .host
+            .context()
+            .commands
+            .push(ScriptCommand::SpawnEnemy {
+              script,
+              x: position.x,
+              y: position.y,
+            })
+        }
+        Effect::Script { script, function } => {
+          self.host.context().target = Some(ScriptTarget {
+            entity: None,
+            x: position.x,
+            y: position.y,
+            angle: position.angle,
+            velocity: (0.0, 0.0),
+            nearest_player: nearest_player(position.x, position.y),
+          });
+          // The entity is gone by now, and its vars with it
+          self.host.context().vars.clear();
+          self.host.call(&script, &function, None);
+        }
+        _ => (),
+      }
+    }
+
+    let commands: Vec<ScriptCommand> = {
+      let mut context = self.host.context();
+      context.target = None;
+      *rng = context.rng.take().unwrap();
+      context.commands.drain(..).collect()
+    };
+
+    for command in commands {
+      match command {
+        ScriptCommand::Spawn { prefab, x, y } => spawn_prefab(
+          prefab,
+          &entities,
+          &lazy,
+          &game_settings,
+          &mut physics,
+          &mut pools,
+          &mut rng,
+          PositionComponent { x, y, angle: 0.0 },
+        ),
+        ScriptCommand::SpawnEnemy { script, x, y } => {
+          if !self.host.has(&script) {
+            eprintln!("No script named '{}' to spawn an enemy from", script);
+            continue;
+          }
+          let stats = self.host.enemy_stats(&script);
+          create_enemy(
+            &entities,
+            &lazy,
+            &game_settings,
+            &mut physics,
+            &script,
+            x,
+            y,
+            stats.radius,
+            stats.hp,
+            stats.score,
+          );
+        }
+        ScriptCommand::SetVelocity { entity, x, y } => {
+          let body = rigid_body
+            .get(entity)
+            .and_then(|rigid_body| physics.world.rigid_body_mut(rigid_body.handle));
+          if let Some(body) = body {
+            body.set_linear_velocity(Vector2::new(x, y));
+          }
+        }
+        ScriptCommand::Emit { name, x, y } => {
+          game_events.single_write(GameEvent::Scripted { name, x, y })
+        }
+      }
+    }
+  }
+
+  fn setup(&mut self, res: &mut Resources) {
+    Self::SystemData::setup(res);
+    self.reader = Some(res.fetch_mut::<EffectEvents>().register_reader());
+  }
+}
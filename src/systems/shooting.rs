@@ -4,7 +4,7 @@ use crate::resources::*;
 
 use specs::world::*;
 use specs::*;
-use std::time::Instant;
+use std::time::Duration;
 
 pub struct ShootingSystem;
 
@@ -13,21 +13,40 @@ impl<'a> System<'a> for ShootingSystem {
     Read<'a, EntitiesRes>,
     Read<'a, LazyUpdate>,
     Read<'a, GameSettings>,
+    Read<'a, UpdateTime>,
     Write<'a, PhysicsSim>,
+    Write<'a, Pools>,
+    Write<'a, GameEvents>,
     ReadStorage<'a, PositionComponent>,
+    ReadStorage<'a, ControllableComponent>,
     WriteStorage<'a, ShootingComponent>,
   );
 
   fn run(
     &mut self,
-    (entities, lazy, game_settings, mut physics, position, mut shooting): Self::SystemData,
+    (
+      entities,
+      lazy,
+      game_settings,
+      update_time,
+      mut physics,
+      mut pools,
+      mut game_events,
+      position,
+      controllable,
+      mut shooting,
+    ): Self::SystemData,
   ) {
-    for (position, shooting) in (&position, &mut shooting).join() {
-      let now = Instant::now();
-      let duration_since_last_shot = now.duration_since(shooting.last_shot_at);
+    for (position, controllable, shooting) in
+      (&position, controllable.maybe(), &mut shooting).join()
+    {
+      // Projectiles from a player's ship are credited to that player
+      let owner = controllable.map(|controllable| controllable.player);
 
-      if duration_since_last_shot >= shooting.every {
-        shooting.last_shot_at = now;
+      shooting.since_last_shot += update_time.0;
+
+      if shooting.since_last_shot >= shooting.every {
+        shooting.since_last_shot = Duration::from_millis(0);
 
         let position_component = PositionComponent {
           x: position.x,
@@ -40,8 +59,15 @@ impl<'a> System<'a> for ShootingSystem {
           &lazy,
           &game_settings,
           &mut physics,
+          &mut pools,
+          owner,
           position_component,
         );
+        game_events.single_write(GameEvent::ShotFired {
+          player: owner,
+          x: position.x,
+          y: position.y,
+        });
       }
     }
   }
@@ -0,0 +1,38 @@
+use nphysics2d::object::BodyStatus;
+use specs::*;
+
+use crate::resources::*;
+
+// Rebuilds the SpatialQuery from wherever the physics step left colliders
+pub struct SpatialIndexSystem;
+
+impl<'a> System<'a> for SpatialIndexSystem {
+  type SystemData = (Read<'a, PhysicsSim>, Write<'a, SpatialQuery>);
+
+  fn run(&mut self, (physics, mut spatial): Self::SystemData) {
+    spatial.clear();
+
+    for collider in physics.world.collider_world().colliders() {
+      let info = match physics.colliders.get(&collider.handle()) {
+        Some(info) => info,
+        None => continue,
+      };
+
+      // Parked pooled bodies are still in the world, but aren't really there
+      let enabled = physics
+        .world
+        .rigid_body(info.body)
+        .map(|body| body.status() != BodyStatus::Disabled)
+        .unwrap_or(true);
+
+      if let (true, Some(entity)) = (enabled, physics.bodies.get(&info.body)) {
+        spatial.insert(
+          *entity,
+          info.collider_type,
+          collider.shape().clone(),
+          *collider.position(),
+        );
+      }
+    }
+  }
+}
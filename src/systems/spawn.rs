@@ -5,7 +5,7 @@ use crate::components::*;
 use crate::entities::*;
 use crate::resources::*;
 
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 pub struct SpawnSystem;
 
@@ -14,21 +14,23 @@ impl<'a> System<'a> for SpawnSystem {
     Read<'a, EntitiesRes>,
     Read<'a, LazyUpdate>,
     Read<'a, GameSettings>,
+    Read<'a, UpdateTime>,
     Write<'a, SpawnInfo>,
     Write<'a, PhysicsSim>,
+    Write<'a, GameRng>,
   );
 
   fn run(
     &mut self,
-    (entities, lazy, game_settings, mut spawn_info, mut physics): Self::SystemData,
+    (entities, lazy, game_settings, update_time, mut spawn_info, mut physics, mut rng): Self::SystemData,
   ) {
-    let now = Instant::now();
+    spawn_info.ammo_since_last += update_time.0;
 
-    if now.duration_since(spawn_info.ammo_last) > spawn_info.ammo_every
+    if spawn_info.ammo_since_last > spawn_info.ammo_every
       && spawn_info.ammo_count < spawn_info.ammo_max
     {
-      create_ammo(&entities, &lazy, &game_settings, &mut physics);
-      spawn_info.ammo_last = now;
+      create_ammo(&entities, &lazy, &game_settings, &mut physics, &mut rng);
+      spawn_info.ammo_since_last = Duration::from_millis(0);
       spawn_info.ammo_count += 1;
     }
   }
@@ -0,0 +1,112 @@
+use specs::world::*;
+use specs::*;
+
+use crate::components::*;
+use crate::resources::*;
+use crate::utils::*;
+
+const SAMPLE_EVERY: f32 = 1.0;
+
+#[derive(Default)]
+pub struct StatsSystem {
+  game_reader: Option<ReaderId<GameEvent>>,
+  damage_reader: Option<ReaderId<DamageEvent>>,
+}
+
+impl<'a> System<'a> for StatsSystem {
+  type SystemData = (
+    Read<'a, EntitiesRes>,
+    Read<'a, UpdateTime>,
+    Read<'a, Score>,
+    Read<'a, PhysicsSim>,
+    Read<'a, Particles>,
+    Read<'a, GameEvents>,
+    Read<'a, DamageEvents>,
+    Write<'a, RunStats>,
+    ReadStorage<'a, ControllableComponent>,
+    ReadStorage<'a, HealthComponent>,
+  );
+
+  fn run(
+    &mut self,
+    (
+      entities,
+      update_time,
+      score,
+      physics,
+      particles,
+      game_events,
+      damage_events,
+      mut stats,
+      controllable,
+      health,
+    ): Self::SystemData,
+  ) {
+    for event in game_events.read(&mut self.game_reader.as_mut().unwrap()) {
+      match event {
+        GameEvent::ShotFired { player, .. } => {
+          stats.shots_fired += 1;
+          if let Some(player) = player.and_then(|p| stats.players.get_mut(p)) {
+            player.shots_fired += 1;
+          }
+        }
+        GameEvent::PickupCollected { kind, player, .. } => {
+          *stats.pickups.entry(kind.to_string()).or_insert(0) += 1;
+          if let Some(player) = player.and_then(|p| stats.players.get_mut(p)) {
+            player.pickups += 1;
+          }
+        }
+        GameEvent::Killed { kind, .. } => *stats.kills.entry(kind.to_string()).or_insert(0) += 1,
+        _ => (),
+      }
+    }
+
+    for event in damage_events.read(&mut self.damage_reader.as_mut().unwrap()) {
+      if let Some(controllable) = controllable.get(event.entity) {
+        stats.damage_taken += event.amount;
+        if let Some(player) = stats.players.get_mut(controllable.player) {
+          player.damage_taken += event.amount;
+        }
+      } else {
+        stats.hits += 1;
+      }
+    }
+
+    let entity_count = (&entities).join().count();
+    let body_count = physics.bodies.len();
+    stats.peak_entities = stats.peak_entities.max(entity_count);
+    stats.peak_bodies = stats.peak_bodies.max(body_count);
+    stats.peak_particles = stats.peak_particles.max(particles.0.len());
+    stats.score = score.0;
+
+    let previous = stats.duration;
+    stats.duration += seconds(update_time.0);
+
+    // Sample once each time the clock passes a whole second
+    if (stats.duration / SAMPLE_EVERY).floor() > (previous / SAMPLE_EVERY).floor() {
+      let hp = (&controllable, &health)
+        .join()
+        .map(|(_, health)| health.hp)
+        .next()
+        .unwrap_or(0.0);
+
+      let sample = StatsSample {
+        time: stats.duration,
+        score: score.0,
+        hp,
+        entities: entity_count,
+        bodies: body_count,
+        particles: particles.0.len(),
+        shots_fired: stats.shots_fired,
+        kills: stats.kills.values().sum(),
+      };
+      stats.samples.push(sample);
+    }
+  }
+
+  fn setup(&mut self, res: &mut Resources) {
+    Self::SystemData::setup(res);
+    self.game_reader = Some(res.fetch_mut::<GameEvents>().register_reader());
+    self.damage_reader = Some(res.fetch_mut::<DamageEvents>().register_reader());
+  }
+}
@@ -0,0 +1,81 @@
+use specs::world::*;
+use specs::*;
+
+use crate::components::*;
+use crate::entities::*;
+use crate::resources::*;
+use crate::utils::*;
+
+use std::time::Duration;
+
+const TRAIL_RADIUS: f32 = 4.0;
+const TRAIL_BOOST_RADIUS: f32 = 6.0;
+const TRAIL_BRAKE_RADIUS: f32 = 3.0;
+const TRAIL_LIFETIME_MS: f32 = 250.0;
+const TRAIL_BOOST_LIFETIME_MS: f32 = 400.0;
+const TRAIL_BRAKE_LIFETIME_MS: f32 = 100.0;
+
+pub struct TrailSystem;
+
+impl<'a> System<'a> for TrailSystem {
+  type SystemData = (
+    Read<'a, EntitiesRes>,
+    Read<'a, LazyUpdate>,
+    Read<'a, UpdateTime>,
+    Write<'a, Pools>,
+    ReadStorage<'a, PositionComponent>,
+    ReadStorage<'a, ControllableComponent>,
+    WriteStorage<'a, TrailComponent>,
+  );
+
+  fn run(
+    &mut self,
+    (entities, lazy, update_time, mut pools, position, controllable, mut trails): Self::SystemData,
+  ) {
+    for (entity, position, trail) in (&entities, &position, &mut trails).join() {
+      trail.elapsed += update_time.0;
+
+      if trail.elapsed < trail.frequency {
+        continue;
+      }
+      trail.elapsed = Duration::from_millis(0);
+
+      let (boost, brake) = controllable
+        .get(entity)
+        .map(|c| (c.boost, c.brake))
+        .unwrap_or((0.0, 0.0));
+
+      let (radius, lifetime_ms) = if boost > 0.0 {
+        (
+          lerp(TRAIL_RADIUS, TRAIL_BOOST_RADIUS, boost),
+          lerp(TRAIL_LIFETIME_MS, TRAIL_BOOST_LIFETIME_MS, boost),
+        )
+      } else {
+        (
+          lerp(TRAIL_RADIUS, TRAIL_BRAKE_RADIUS, brake),
+          lerp(TRAIL_LIFETIME_MS, TRAIL_BRAKE_LIFETIME_MS, brake),
+        )
+      };
+      let color = if boost > 0.0 {
+        boost_color()
+      } else {
+        trail_color()
+      };
+
+      let (cos, sin) = (position.angle.cos(), position.angle.sin());
+
+      for (ax, ay) in trail.attachments.iter() {
+        create_trail_particle(
+          &entities,
+          &lazy,
+          &mut pools,
+          position.x + ax * cos - ay * sin,
+          position.y + ax * sin + ay * cos,
+          radius,
+          color,
+          Duration::from_millis(lifetime_ms as u64),
+        );
+      }
+    }
+  }
+}
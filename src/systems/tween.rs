@@ -1,12 +1,10 @@
 use crate::components::*;
-use crate::entities::*;
 use crate::resources::*;
+use crate::utils::*;
 
-use easer::functions::*;
+use ggez::graphics;
 use specs::world::*;
 use specs::*;
-use std::ops::Add;
-use std::time::Duration;
 
 pub struct TweenSystem;
 
@@ -14,53 +12,147 @@ impl<'a> System<'a> for TweenSystem {
   type SystemData = (
     Read<'a, EntitiesRes>,
     Read<'a, UpdateTime>,
+    Write<'a, EffectEvents>,
+    ReadStorage<'a, PositionComponent>,
     WriteStorage<'a, TweenComponent>,
     WriteStorage<'a, MeshComponent>,
+    WriteStorage<'a, GarbageComponent>,
   );
 
-  fn run(&mut self, (entities, update_time, mut tweens, mut mesh): Self::SystemData) {
+  fn run(
+    &mut self,
+    (
+      entities,
+      update_time,
+      mut effect_events,
+      position,
+      mut tweens,
+      mut mesh,
+      mut garbage,
+    ): Self::SystemData,
+  ) {
+    let mut completed = vec![];
+
     for (entity, tween) in (&entities, &mut tweens).join() {
-      if tween.elapsed.as_millis() <= tween.duration.as_millis() {
-        let dt = update_time.0;
-        tween.elapsed = tween.elapsed.add(dt);
-        match &tween.tween_type {
-          Tween::SizeTween {
-            ease,
-            starting,
-            ending,
-          } => {
-            let tweened_value = if starting > ending {
-              let tweened_value = match ease {
-                Ease::Cubic => Cubic::ease_in(
-                  tween.elapsed.as_millis() as f32,
-                  *ending,
-                  *starting,
-                  tween.duration.as_millis() as f32,
-                ),
-              };
-              *starting - tweened_value
-            } else {
-              match ease {
-                Ease::Cubic => Cubic::ease_in(
-                  tween.elapsed.as_millis() as f32,
-                  *ending,
-                  *starting,
-                  tween.duration.as_millis() as f32,
-                ),
-              }
-            };
+      if tween.completed {
+        continue;
+      }
 
-            let mesh: Option<&mut MeshComponent> = mesh.get_mut(entity);
+      tween.elapsed += update_time.0;
+      let elapsed = seconds(tween.elapsed);
+
+      if let Some(mesh) = mesh.get_mut(entity) {
+        apply_node(&tween.tween, elapsed, &mut mesh.draw_param);
+      }
+
+      if let Some(duration) = tween.tween.duration() {
+        if elapsed >= duration {
+          tween.completed = true;
+          completed.push((entity, tween.on_complete.clone()));
+        }
+      }
+    }
 
-            if let Some(mesh) = mesh {
-              (mesh).draw_param = ggez::graphics::DrawParam {
-                scale: ggez::graphics::Point2::new(tweened_value, tweened_value),
-                ..mesh.draw_param
-              };
+    for (entity, actions) in completed {
+      for action in actions {
+        match action {
+          TweenAction::Garbage => {
+            garbage.get_mut(entity).map(|g| g.is_alive = false);
+          }
+          TweenAction::Effect(effect) => {
+            if let Some(position) = position.get(entity) {
+              effect_events.single_write(EffectEvent {
+                effect,
+                position: position.clone(),
+              });
             }
           }
-        };
+        }
+      }
+    }
+  }
+}
+
+// Applies the state of a tween tree `time` seconds in
+fn apply_node(node: &TweenNode, time: f32, draw_param: &mut graphics::DrawParam) {
+  match node {
+    TweenNode::Tween {
+      tween_type,
+      ease,
+      duration,
+    } => {
+      let duration = seconds(*duration);
+      let t = if duration > 0.0 {
+        (time / duration).min(1.0)
+      } else {
+        1.0
+      };
+      apply_tween(tween_type, ease.ratio(t), draw_param);
+    }
+    TweenNode::Delay(_) => {}
+    TweenNode::Sequence(nodes) => {
+      let mut start = 0.0;
+      for node in nodes {
+        if time < start {
+          break;
+        }
+        apply_node(node, time - start, draw_param);
+        match node.duration() {
+          Some(duration) => start += duration,
+          None => break,
+        }
       }
     }
+    TweenNode::Parallel(nodes) => {
+      for node in nodes {
+        apply_node(node, time, draw_param);
+      }
+    }
+    TweenNode::Repeat { node, times, yoyo } => {
+      let duration = match node.duration() {
+        Some(duration) if duration > 0.0 => duration,
+        _ => return apply_node(node, time, draw_param),
+      };
+
+      let (iteration, local) = match times {
+        Some(times) if time >= duration * *times as f32 => (times.saturating_sub(1), duration),
+        _ => {
+          let iteration = (time / duration).floor();
+          (iteration as u32, time - iteration * duration)
+        }
+      };
+
+      let local = if *yoyo && iteration % 2 == 1 {
+        duration - local
+      } else {
+        local
+      };
+
+      apply_node(node, local, draw_param);
+    }
+  }
+}
+
+fn apply_tween(tween: &Tween, t: f32, draw_param: &mut graphics::DrawParam) {
+  match tween {
+    Tween::SizeTween { starting, ending } => {
+      let size = lerp(*starting, *ending, t);
+      draw_param.scale = graphics::Point2::new(size, size);
+    }
+    Tween::AlphaTween { starting, ending } => {
+      let mut color = draw_param.color.unwrap_or(graphics::WHITE);
+      color.a = lerp(*starting, *ending, t);
+      draw_param.color = Some(color);
+    }
+    Tween::ColorTween { starting, ending } => {
+      draw_param.color = Some(lerp_color(*starting, *ending, t));
+    }
+    Tween::OffsetTween { starting, ending } => {
+      draw_param.dest =
+        graphics::Point2::new(lerp(starting.0, ending.0, t), lerp(starting.1, ending.1, t));
+    }
+    Tween::RotationTween { starting, ending } => {
+      draw_param.rotation = lerp(*starting, *ending, t);
+    }
   }
 }
@@ -1,4 +1,5 @@
 use ggez::graphics;
+use std::time::Duration;
 
 pub fn backround_color() -> graphics::Color {
   graphics::Color::from_rgb(16, 16, 16)
@@ -12,6 +13,35 @@ pub fn boost_color() -> graphics::Color {
 pub fn hp_color() -> graphics::Color {
   graphics::Color::from_rgb(241, 103, 69)
 }
+pub fn trail_color() -> graphics::Color {
+  graphics::Color::from_rgb(255, 198, 93)
+}
+pub fn enemy_color() -> graphics::Color {
+  graphics::Color::from_rgb(194, 111, 236)
+}
+pub fn player_color(player: usize) -> graphics::Color {
+  match player {
+    0 => graphics::WHITE,
+    _ => boost_color(),
+  }
+}
+
+pub fn seconds(duration: Duration) -> f32 {
+  duration.as_secs() as f32 + duration.subsec_nanos() as f32 / 1_000_000_000.0
+}
+
+pub fn lerp(from: f32, to: f32, t: f32) -> f32 {
+  from + (to - from) * t
+}
+
+pub fn lerp_color(from: graphics::Color, to: graphics::Color, t: f32) -> graphics::Color {
+  graphics::Color::new(
+    lerp(from.r, to.r, t),
+    lerp(from.g, to.g, t),
+    lerp(from.b, to.b, t),
+    lerp(from.a, to.a, t),
+  )
+}
 
 pub fn rect_to_polygon(rect: graphics::Rect) -> Vec<graphics::Point2> {
   let x1 = rect.x;
@@ -25,3 +55,64 @@ pub fn rect_to_polygon(rect: graphics::Rect) -> Vec<graphics::Point2> {
     graphics::Point2::new(x1, y2),
   ]
 }
+
+pub const AXIS_DEADZONE: f32 = 0.2;
+
+// Maps a raw SDL controller axis value onto -1.0..=1.0 (0.0..=1.0 for
+// triggers), snapping anything inside the deadzone to zero.
+pub fn normalize_axis(value: i16) -> f32 {
+  let normalized = (value as f32 / std::i16::MAX as f32).max(-1.0).min(1.0);
+  if normalized.abs() < AXIS_DEADZONE {
+    0.0
+  } else {
+    normalized
+  }
+}
+
+// serde adapters for the ggez types that components keep, which don't
+// implement Serialize themselves. Use with #[serde(with = "...")].
+pub mod serde_color {
+  use ggez::graphics::Color;
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+  pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+    [color.r, color.g, color.b, color.a].serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let [r, g, b, a] = <[f32; 4]>::deserialize(deserializer)?;
+    Ok(Color::new(r, g, b, a))
+  }
+}
+
+pub mod serde_option_color {
+  use ggez::graphics::Color;
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+  pub fn serialize<S: Serializer>(color: &Option<Color>, serializer: S) -> Result<S::Ok, S::Error> {
+    color
+      .map(|color| [color.r, color.g, color.b, color.a])
+      .serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Option<Color>, D::Error> {
+    let color = Option::<[f32; 4]>::deserialize(deserializer)?;
+    Ok(color.map(|[r, g, b, a]| Color::new(r, g, b, a)))
+  }
+}
+
+pub mod serde_point2 {
+  use ggez::graphics::Point2;
+  use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+  pub fn serialize<S: Serializer>(point: &Point2, serializer: S) -> Result<S::Ok, S::Error> {
+    [point.x, point.y].serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Point2, D::Error> {
+    let [x, y] = <[f32; 2]>::deserialize(deserializer)?;
+    Ok(Point2::new(x, y))
+  }
+}
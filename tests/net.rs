@@ -0,0 +1,113 @@
+// Runs two headless peers against each other over loopback
+use std::fs;
+use std::net::UdpSocket;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+
+const BIN: &str = env!("CARGO_BIN_EXE_bytepath-rs");
+const TICKS: usize = 180;
+
+// Lets the OS pick a port nobody is using
+fn free_port() -> u16 {
+  UdpSocket::bind("127.0.0.1:0")
+    .and_then(|socket| socket.local_addr())
+    .map(|addr| addr.port())
+    .unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("bytepath-net-{}-{}", name, std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir
+}
+
+// Player one turns and fires, player two thrusts; the same file drives both peers
+fn write_replay(dir: &Path) -> PathBuf {
+  let mut lines = String::new();
+  for tick in 0..TICKS {
+    let one = input(false, tick % 60 < 30, tick % 10 == 0);
+    let two = input(tick % 40 < 20, false, false);
+    lines.push_str(&format!("[{},{}]\n", one, two));
+  }
+
+  let path = dir.join("replay.jsonl");
+  fs::write(&path, lines).unwrap();
+  path
+}
+
+fn input(up: bool, left: bool, attack: bool) -> String {
+  format!(
+    "{{\"up\":{},\"down\":false,\"left\":{},\"right\":false,\"attack\":{},\
+     \"turn_axis\":0.0,\"boost_axis\":0.0,\"brake_axis\":0.0}}",
+    up, left, attack
+  )
+}
+
+fn spawn_peer(
+  player: usize,
+  bind: u16,
+  peer: u16,
+  replay: &Path,
+  dir: &Path,
+  extra: &[&str],
+) -> Child {
+  Command::new(BIN)
+    .args(&["--headless", "--seed", "42"])
+    .args(&["--net-bind", &format!("127.0.0.1:{}", bind)])
+    .args(&["--net-peer", &format!("127.0.0.1:{}", peer)])
+    .args(&["--net-player", &player.to_string()])
+    .arg("--replay")
+    .arg(replay)
+    .arg("--stats-dir")
+    .arg(dir.join(format!("p{}", player)))
+    .args(extra)
+    .spawn()
+    .unwrap()
+}
+
+// Both peers' exit statuses, and any desync reports they wrote
+fn run_pair(name: &str, extra_two: &[&str]) -> (bool, bool, Vec<PathBuf>) {
+  let dir = temp_dir(name);
+  let replay = write_replay(&dir);
+  let (one_port, two_port) = (free_port(), free_port());
+
+  let mut one = spawn_peer(1, one_port, two_port, &replay, &dir, &[]);
+  let mut two = spawn_peer(2, two_port, one_port, &replay, &dir, extra_two);
+  let one_ok = one.wait().unwrap().success();
+  let two_ok = two.wait().unwrap().success();
+
+  let mut reports = Vec::new();
+  for player in 1..=2 {
+    if let Ok(entries) = fs::read_dir(dir.join(format!("p{}", player))) {
+      for entry in entries {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if name.starts_with("desync-") {
+          reports.push(path);
+        }
+      }
+    }
+  }
+
+  (one_ok, two_ok, reports)
+}
+
+#[test]
+fn peers_stay_in_sync() {
+  let (one_ok, two_ok, reports) = run_pair("sync", &[]);
+  assert!(one_ok && two_ok);
+  assert!(
+    reports.is_empty(),
+    "unexpected desync reports: {:?}",
+    reports
+  );
+}
+
+#[test]
+fn desync_writes_a_report() {
+  // Player two's ship can't fire, so their world drifts from player one's
+  let (one_ok, two_ok, reports) = run_pair("desync", &["--pool-cap", "projectile=0"]);
+  assert!(!(one_ok && two_ok));
+  assert!(!reports.is_empty());
+}